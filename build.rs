@@ -2,10 +2,33 @@ use std::env;
 use std::path::PathBuf;
 
 fn main() {
-    let path = PathBuf::from(env::current_dir().unwrap());
+    // packagers and CI can point this at wherever the usvfs binaries were unpacked,
+    // without it the library is expected next to the crate's manifest
+    println!("cargo:rerun-if-env-changed=USVFS_LIB_DIR");
+    let path = match env::var_os("USVFS_LIB_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::current_dir().unwrap(),
+    };
     println!(
         "cargo:rustc-link-search=native={}",
         path.as_os_str().to_str().unwrap()
     );
-    println!("cargo:rustc-link-lib=usvfs_x64");
+
+    // usvfs ships a separate library for each bitness
+    let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let library = match arch.as_str() {
+        "x86_64" => "usvfs_x64",
+        "x86" => "usvfs_x86",
+        _ => panic!("usvfs is only available for x86_64 and x86, not {}", arch),
+    };
+    println!("cargo:rustc-link-lib={}", library);
+
+    // with delay loading the DLL is only loaded on the first call into it,
+    // so the process can start without it and load it through Usvfs::load().
+    // link args only reach this crate's own tests and examples, applications
+    // emit the same two lines from their build script, see Usvfs
+    if env::var_os("CARGO_FEATURE_DELAY_LOAD").is_some() {
+        println!("cargo:rustc-link-arg=/DELAYLOAD:{}.dll", library);
+        println!("cargo:rustc-link-lib=delayimp");
+    }
 }
@@ -21,6 +21,7 @@
         toolchain = with fenix.packages.${system}; combine [
           minimal.cargo
           minimal.rustc
+          default.clippy
           targets.${target}.latest.rust-std
         ];
 
@@ -36,6 +37,16 @@
           strctDeps = true;
         };
 
+        # nix flake check, the same as
+        # cargo clippy --target x86_64-pc-windows-msvc --all-targets --all-features -- -D warnings
+        checks.clippy = naersk'.buildPackage {
+          src = ./.;
+          mode = "clippy";
+          CARGO_BUILD_TARGET = target;
+          cargoBuildOptions = x: x ++ [ "--all-targets" "--all-features" ];
+          cargoClippyOptions = x: x ++ [ "-D" "warnings" ];
+        };
+
         depsBuildBuild = with pkgs; [
           pkgsCross.mingwW64.stdenv.cc
           pkgsCross.mingwW64.windows.pthreads
@@ -0,0 +1,46 @@
+use std::ffi::CStr;
+
+use crate::usvfsVersionString;
+
+/// What the usvfs library this crate is linked against can do.
+///
+/// usvfs has no call to ask for its features, and every build exports the
+/// same functions, so there is nothing to probe for. Instead the version is
+/// read from usvfsVersionString() and the flags say what this crate can offer
+/// with it. Operations upstream only planned, like dynamic directory links
+/// and copy-on-write, aren't exported by any usvfs release so far and are
+/// always false. They will be derived from the version once a release adds them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// the version string the library reports, like `0.5.6.1`
+    pub version: String,
+    /// the dot separated numbers of version, None if it doesn't parse
+    pub version_numbers: Option<Vec<u32>>,
+    /// directory links that pick up files added to the source later,
+    /// see virtually_link_directory_static()
+    pub supports_dynamic_directory: bool,
+    /// writes to linked files going to a copy instead of the original
+    pub supports_cow: bool,
+}
+
+/// the version string of the linked usvfs library
+pub fn usvfs_version() -> String {
+    unsafe { CStr::from_ptr(usvfsVersionString().cast()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// what the linked usvfs library can do, see Capabilities
+pub fn capabilities() -> Capabilities {
+    let version = usvfs_version();
+    let version_numbers = version
+        .split('.')
+        .map(|number| number.trim().parse().ok())
+        .collect::<Option<Vec<u32>>>();
+    Capabilities {
+        version,
+        version_numbers,
+        supports_dynamic_directory: false,
+        supports_cow: false,
+    }
+}
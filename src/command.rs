@@ -0,0 +1,189 @@
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use windows::Win32::System::Threading::{
+    CREATE_UNICODE_ENVIRONMENT, PROCESS_CREATION_FLAGS, PROCESS_INFORMATION,
+};
+
+use crate::{
+    create_process_hooked_wide, CreateProcessOptions, EnvBlock, HookedProcess, UsvfsError,
+};
+
+/// A builder for spawning a process into the VFS, modeled on
+/// std::process::Command. The environment is inherited from the calling
+/// process, or taken from environment(), with the variables set through
+/// env() and envs() on top
+#[derive(Debug, Clone)]
+pub struct HookedCommand {
+    application: PathBuf,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    environment: Option<EnvBlock>,
+    env: Vec<(OsString, OsString)>,
+    creation_flags: PROCESS_CREATION_FLAGS,
+}
+
+impl HookedCommand {
+    /// a command running application, which has to be a full path since
+    /// CreateProcess does not search PATH for it
+    pub fn new(application: impl AsRef<Path>) -> Self {
+        Self {
+            application: application.as_ref().to_owned(),
+            args: Vec::new(),
+            current_dir: None,
+            environment: None,
+            env: Vec::new(),
+            creation_flags: PROCESS_CREATION_FLAGS(0),
+        }
+    }
+
+    /// append an argument, it is quoted as needed
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// append several arguments, see arg()
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// the working directory of the process, the caller's one if unset
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.current_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// set an environment variable for the process. Names are case
+    /// insensitive like on Windows, so setting PATH replaces Path
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.env
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// start from environment instead of the caller's environment
+    pub fn environment(&mut self, environment: EnvBlock) -> &mut Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// set several environment variables, see env()
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (key, value) in vars {
+            self.env(key, value);
+        }
+        self
+    }
+
+    /// flags passed on to CreateProcess, see create_process_hooked()
+    pub fn creation_flags(&mut self, flags: PROCESS_CREATION_FLAGS) -> &mut Self {
+        self.creation_flags = flags;
+        self
+    }
+
+    /// the command line the process is started with, the application
+    /// followed by the arguments, quoted the way std::process::Command does
+    pub fn command_line(&self) -> Result<Vec<u16>, UsvfsError> {
+        let mut command_line = Vec::new();
+        append_arg(&mut command_line, self.application.as_os_str(), true)?;
+        for arg in &self.args {
+            command_line.push(u16::from(b' '));
+            append_arg(&mut command_line, arg, false)?;
+        }
+        command_line.push(0x00);
+        Ok(command_line)
+    }
+
+    /// spawn the process into the VFS the calling process is connected to
+    pub fn spawn(&self) -> Result<HookedProcess, UsvfsError> {
+        let mut command_line = self.command_line()?;
+        let environment = match (&self.environment, self.env.is_empty()) {
+            (None, true) => None,
+            (environment, _) => {
+                let mut environment = environment.clone().unwrap_or_else(EnvBlock::from_current);
+                for (key, value) in &self.env {
+                    environment.set(key, value)?;
+                }
+                Some(environment.encode())
+            }
+        };
+        let mut creation_flags = self.creation_flags;
+        if environment.is_some() {
+            creation_flags |= CREATE_UNICODE_ENVIRONMENT;
+        }
+
+        let mut process_information = PROCESS_INFORMATION::default();
+        create_process_hooked_wide(
+            &self.application,
+            &mut command_line,
+            environment.as_deref(),
+            CreateProcessOptions {
+                creation_flags,
+                current_dir: self.current_dir.as_deref(),
+                process_information: Some(&mut process_information),
+                ..Default::default()
+            },
+        )?;
+        unsafe { Ok(HookedProcess::from_raw(process_information)) }
+    }
+}
+
+/// append arg to command_line so CommandLineToArgvW and the C runtime read it
+/// back unchanged. Backslashes are only special in front of a quote, so those
+/// runs are doubled and the quote escaped
+fn append_arg(
+    command_line: &mut Vec<u16>,
+    arg: &OsStr,
+    force_quotes: bool,
+) -> Result<(), UsvfsError> {
+    const QUOTE: u16 = b'"' as u16;
+    const BACKSLASH: u16 = b'\\' as u16;
+
+    let arg: Vec<u16> = arg.encode_wide().collect();
+    if arg.contains(&0x00) {
+        return Err(UsvfsError::InteriorNul);
+    }
+    let quote = force_quotes
+        || arg.is_empty()
+        || arg
+            .iter()
+            .any(|&unit| unit == u16::from(b' ') || unit == u16::from(b'\t'));
+
+    if quote {
+        command_line.push(QUOTE);
+    }
+    let mut backslashes = 0;
+    for unit in arg {
+        match unit {
+            BACKSLASH => backslashes += 1,
+            QUOTE => {
+                command_line.extend(std::iter::repeat_n(BACKSLASH, backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        command_line.push(unit);
+    }
+    if quote {
+        // the closing quote must not be escaped by trailing backslashes
+        command_line.extend(std::iter::repeat_n(BACKSLASH, backslashes));
+        command_line.push(QUOTE);
+    }
+    Ok(())
+}
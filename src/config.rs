@@ -0,0 +1,67 @@
+use std::{path::PathBuf, time};
+
+use crate::{CrashDumpsType, LogLevel, ParametersHandle, UsvfsError};
+
+/// Plain Rust copy of the VFS parameters, for keeping the configuration
+/// somewhere the opaque usvfs type can't go, like a config file. With the
+/// serde feature enabled it can be serialized and deserialized, the log level
+/// and crash dumps type use the same lowercase names as their Display output
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VfsConfig {
+    /// the name for the VFS instance
+    pub instance_name: String,
+    /// whether the VFS should output debug information
+    pub debug_mode: bool,
+    /// the VFS log level
+    pub log_level: LogLevel,
+    /// the VFS crash dumps type
+    pub crash_dumps_type: CrashDumpsType,
+    /// the path for crash dumps, empty dumps to the current working directory
+    pub crash_dumps_path: PathBuf,
+    /// the amount of time to delay the process in milliseconds
+    pub process_delay_ms: u64,
+}
+
+impl Default for VfsConfig {
+    /// a starting point to change single fields of: no instance name, which
+    /// has to be set before creating a VFS, debug mode off, Info logging,
+    /// no crash dumps, dumping to the current working directory and no delay
+    fn default() -> Self {
+        Self {
+            instance_name: String::new(),
+            debug_mode: false,
+            log_level: LogLevel::Info,
+            crash_dumps_type: CrashDumpsType::Nil,
+            crash_dumps_path: PathBuf::new(),
+            process_delay_ms: 0,
+        }
+    }
+}
+
+impl VfsConfig {
+    /// set every parameter in params to the value in this config. Fails if
+    /// the instance name or crash dumps path contains a null byte, or the
+    /// instance name is longer than MAX_INSTANCE_NAME_LEN
+    pub fn apply_to(&self, params: &mut ParametersHandle) -> Result<(), UsvfsError> {
+        params.set_instance_name(&self.instance_name)?;
+        params.set_debug_mode(self.debug_mode);
+        params.set_log_level(self.log_level);
+        params.set_crash_dumps_type(self.crash_dumps_type);
+        params.set_crash_dumps_path(&self.crash_dumps_path)?;
+        params.set_process_delay(time::Duration::from_millis(self.process_delay_ms));
+        Ok(())
+    }
+}
+
+impl TryFrom<VfsConfig> for ParametersHandle {
+    type Error = UsvfsError;
+
+    /// new parameters with every field of config applied, see
+    /// VfsConfig::apply_to() for how this can fail
+    fn try_from(config: VfsConfig) -> Result<Self, Self::Error> {
+        let mut params = ParametersHandle::new();
+        config.apply_to(&mut params)?;
+        Ok(params)
+    }
+}
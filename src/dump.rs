@@ -0,0 +1,242 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+/// Whether a node of the VFS tree is a file or a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    File,
+    Directory,
+}
+
+/// One node of the VFS tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsNode {
+    /// the name of this node, without its ancestors
+    pub name: String,
+    /// the full path of this node inside the VFS
+    pub virtual_path: PathBuf,
+    /// the real path this node is redirected to, if any
+    pub source: Option<PathBuf>,
+    pub kind: NodeKind,
+    pub children: Vec<VfsNode>,
+}
+
+/// A VFS tree parsed from the output of vfs_dump()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsTree {
+    pub root: VfsNode,
+}
+
+impl VfsTree {
+    /// find the node at virtual_path. Names are compared case insensitively
+    /// like Windows does, and both separators are accepted
+    pub fn find(&self, virtual_path: impl AsRef<Path>) -> Option<&VfsNode> {
+        let virtual_path = virtual_path.as_ref().to_string_lossy();
+        let mut node = &self.root;
+        for name in virtual_path
+            .split(['\\', '/'])
+            .filter(|name| !name.is_empty())
+        {
+            let name = name.to_lowercase();
+            node = node
+                .children
+                .iter()
+                .find(|child| child.name.to_lowercase() == name)?;
+        }
+        Some(node)
+    }
+
+    /// every node of the tree, depth first: each node comes before its
+    /// children, and children come in the order the dump lists them.
+    /// The root is the first node
+    pub fn iter(&self) -> impl Iterator<Item = &VfsNode> {
+        Iter {
+            stack: vec![&self.root],
+        }
+    }
+
+    /// the file nodes of the tree, in the order iter() visits them
+    pub fn iter_files(&self) -> impl Iterator<Item = &VfsNode> {
+        self.iter().filter(|node| node.kind == NodeKind::File)
+    }
+
+    /// the directory nodes of the tree, in the order iter() visits them
+    pub fn iter_directories(&self) -> impl Iterator<Item = &VfsNode> {
+        self.iter().filter(|node| node.kind == NodeKind::Directory)
+    }
+}
+
+/// depth first walk over a VfsTree, see VfsTree::iter()
+struct Iter<'a> {
+    /// the nodes still to visit, the next one on top
+    stack: Vec<&'a VfsNode>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a VfsNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+/// Node counts of a VFS dump, see count_dump_nodes()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DumpStats {
+    /// nodes without children, taken to be files like parse_vfs_dump() does
+    pub files: usize,
+    /// nodes with children, not counting the unnamed root
+    pub directories: usize,
+    /// nodes redirected to a real path, files and directories alike
+    pub redirected: usize,
+}
+
+/// count the nodes of the text from vfs_dump() without building the tree.
+///
+/// Unlike parse_vfs_dump() this doesn't check the layout, so it still gives
+/// counts for dumps of formats the parser doesn't know. Every line with a
+/// `->` marker is a node, anything else is ignored, and a node is a
+/// directory if the node after it is indented further. The dump doesn't
+/// list sizes, so the bytes behind the mapped files can't be counted
+pub fn count_dump_nodes(dump: &str) -> DumpStats {
+    let nodes: Vec<(usize, &str, &str)> = dump
+        .lines()
+        .filter_map(|line| {
+            let (indented_name, source) = line.split_once("->")?;
+            let name = indented_name.trim_start();
+            Some((indented_name.len() - name.len(), name.trim(), source.trim()))
+        })
+        .collect();
+
+    let mut stats = DumpStats::default();
+    for (index, (depth, name, source)) in nodes.iter().enumerate() {
+        if name.is_empty() {
+            continue;
+        }
+        match nodes.get(index + 1).is_some_and(|(next, ..)| next > depth) {
+            true => stats.directories += 1,
+            false => stats.files += 1,
+        }
+        if !source.is_empty() {
+            stats.redirected += 1;
+        }
+    }
+    stats
+}
+
+/// Error for a dump that doesn't match the expected format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// the 1-based line of the dump the error was found at
+    pub line: usize,
+    pub reason: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid VFS dump at line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// parse the text from vfs_dump() into a tree.
+///
+/// The dump format is not fixed and may change between usvfs versions, so
+/// each known format gets its own parser and this picks the right one. Only
+/// the format current usvfs writes is known so far, see parse_v1()
+pub fn parse_vfs_dump(dump: &str) -> Result<VfsTree, ParseError> {
+    parse_v1(dump)
+}
+
+/// parses the dump format written by usvfs's dumpTree(). Every node is a line
+/// of `name -> source`, indented by one space per level below the root, with
+/// an empty source for nodes that aren't redirected:
+///
+/// ```text
+///  ->
+///  c: ->
+///   games ->
+///    data -> c:\mods\a\data
+///     plugin.esp -> c:\mods\a\data\plugin.esp
+/// ```
+///
+/// The dump doesn't say whether a node is a file or a directory, so nodes
+/// with children are taken to be directories and leaves to be files
+fn parse_v1(dump: &str) -> Result<VfsTree, ParseError> {
+    // the chain of nodes from the root to the one parsed last, with their depth
+    let mut stack: Vec<(usize, VfsNode)> = Vec::new();
+    let mut root: Option<VfsNode> = None;
+
+    for (index, line) in dump.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |reason: &str| ParseError {
+            line: index + 1,
+            reason: reason.to_owned(),
+        };
+
+        let (indented_name, source) = line
+            .split_once(" -> ")
+            .or_else(|| line.strip_suffix(" ->").map(|name| (name, "")))
+            .ok_or_else(|| error("expected \"name -> source\""))?;
+        let name = indented_name.trim_start_matches(' ');
+        let depth = indented_name.len() - name.len();
+
+        while stack.last().is_some_and(|(top, _)| *top >= depth) {
+            close_node(&mut stack, &mut root);
+        }
+        if root.is_some() {
+            return Err(error("more than one root node"));
+        }
+        let virtual_path = match stack.last() {
+            Some((top, _)) if depth != top + 1 => {
+                return Err(error("indented more than one level below its parent"))
+            }
+            Some((_, parent)) => match parent.virtual_path.as_os_str().is_empty() {
+                true => name.to_owned(),
+                false => format!("{}\\{}", parent.virtual_path.display(), name),
+            },
+            None => name.to_owned(),
+        };
+
+        stack.push((
+            depth,
+            VfsNode {
+                name: name.to_owned(),
+                virtual_path: PathBuf::from(virtual_path),
+                source: (!source.is_empty()).then(|| PathBuf::from(source)),
+                kind: NodeKind::File,
+                children: Vec::new(),
+            },
+        ));
+    }
+
+    while !stack.is_empty() {
+        close_node(&mut stack, &mut root);
+    }
+    root.map(|root| VfsTree { root }).ok_or(ParseError {
+        line: 0,
+        reason: "dump is empty".to_owned(),
+    })
+}
+
+/// pop the innermost node off the stack and attach it to its parent,
+/// or make it the root if it has none
+fn close_node(stack: &mut Vec<(usize, VfsNode)>, root: &mut Option<VfsNode>) {
+    if let Some((_, mut node)) = stack.pop() {
+        if !node.children.is_empty() {
+            node.kind = NodeKind::Directory;
+        }
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.push(node),
+            None => *root = Some(node),
+        }
+    }
+}
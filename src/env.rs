@@ -0,0 +1,101 @@
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::OsStrExt,
+};
+
+use crate::UsvfsError;
+
+/// An environment for a hooked process, encoded into the block
+/// CreateProcessW takes. Names are case insensitive like on Windows,
+/// so setting PATH replaces an existing Path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvBlock {
+    vars: Vec<(OsString, OsString)>,
+}
+
+impl EnvBlock {
+    /// an environment without any variables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the environment of the calling process
+    pub fn from_current() -> Self {
+        Self {
+            vars: std::env::vars_os().collect(),
+        }
+    }
+
+    /// set key to value, replacing the variable if it is set under any
+    /// case. Fails with InteriorNul if either contains a null, and with
+    /// ParseFailed if the key is empty or has a = past its first character,
+    /// which would end the name early
+    pub fn set(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<&mut Self, UsvfsError> {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        let wide_key: Vec<u16> = key.encode_wide().collect();
+        if wide_key.contains(&0x00) || value.encode_wide().any(|unit| unit == 0x00) {
+            return Err(UsvfsError::InteriorNul);
+        }
+        if wide_key.is_empty() || wide_key[1..].contains(&u16::from(b'=')) {
+            return Err(UsvfsError::ParseFailed {
+                kind: "environment variable name",
+                input: key.to_string_lossy().into_owned(),
+            });
+        }
+
+        self.remove(key);
+        self.vars.push((key.to_owned(), value.to_owned()));
+        Ok(self)
+    }
+
+    /// unset key under any case
+    pub fn remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        let key = sort_key(key.as_ref());
+        self.vars.retain(|(existing, _)| sort_key(existing) != key);
+        self
+    }
+
+    /// the value of key under any case
+    pub fn get(&self, key: impl AsRef<OsStr>) -> Option<&OsStr> {
+        let key = sort_key(key.as_ref());
+        self.vars
+            .iter()
+            .find(|(existing, _)| sort_key(existing) == key)
+            .map(|(_, value)| value.as_os_str())
+    }
+
+    /// encode as `name=value` strings, each null terminated and sorted by
+    /// name without regard to case, followed by a final null. Pass it to
+    /// CreateProcessW together with CREATE_UNICODE_ENVIRONMENT
+    pub fn encode(&self) -> Vec<u16> {
+        let mut vars: Vec<&(OsString, OsString)> = self.vars.iter().collect();
+        vars.sort_by_cached_key(|(key, _)| sort_key(key));
+
+        let mut block = Vec::new();
+        for (key, value) in vars {
+            block.extend(key.encode_wide());
+            block.push(u16::from(b'='));
+            block.extend(value.encode_wide());
+            block.push(0x00);
+        }
+        // an empty block still needs both nulls
+        if block.is_empty() {
+            block.push(0x00);
+        }
+        block.push(0x00);
+        block
+    }
+}
+
+/// the form names are compared and sorted in, Windows orders the block
+/// by the uppercase names
+fn sort_key(key: &OsStr) -> Vec<u16> {
+    key.to_string_lossy()
+        .to_uppercase()
+        .encode_utf16()
+        .collect()
+}
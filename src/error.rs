@@ -0,0 +1,245 @@
+use std::{
+    fmt::{Display, Formatter},
+    io,
+};
+
+use windows::{
+    core::HRESULT,
+    Win32::Foundation::{GetLastError, ERROR_ACCESS_DENIED, ERROR_ALREADY_EXISTS, WIN32_ERROR},
+};
+
+use crate::{LinkFlags, MappingKind};
+
+/// Errors returned by the usvfs wrappers. Where usvfs reports failure
+/// through a bool, the Win32 last error code at the time of the failure
+/// is attached since it is the only diagnostic the library leaves behind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsvfsError {
+    /// usvfsCreateVFS failed for a reason without its own variant
+    CreateFailed { code: u32 },
+    /// usvfsCreateVFS failed because the shared memory of the instance
+    /// already exists, eg since a previous run didn't disconnect. The
+    /// VFS can usually be connected to instead
+    AlreadyExists,
+    /// usvfsCreateVFS was denied access to the shared memory of the instance
+    AccessDenied,
+    /// usvfsConnectVFS failed
+    ConnectFailed { code: u32 },
+    /// linking source to destination failed
+    LinkFailed {
+        source: String,
+        destination: String,
+        code: u32,
+    },
+    /// spawning a hooked process failed. The code is read right after
+    /// usvfsCreateProcessHooked returns, so it is whatever CreateProcess or
+    /// the injection of the hooks that followed it failed with
+    ProcessSpawnFailed {
+        application_name: String,
+        win32_code: u32,
+    },
+    /// creating a dump of the VFS tree failed
+    DumpFailed { code: u32 },
+    /// retrieving the list of hooked processes failed
+    ProcessListFailed { code: u32 },
+    /// waiting for a hooked process to exit failed
+    WaitFailed { pid: u32, code: u32 },
+    /// no process has the pid, it may have exited already
+    NoSuchProcess { pid: u32 },
+    /// resuming the main thread of a suspended process failed
+    ResumeFailed { pid: u32, code: u32 },
+    /// closing a process or thread handle failed
+    CloseFailed { code: u32 },
+    /// no instance name, or an empty one, was given
+    EmptyInstanceName,
+    /// the instance name is longer than MAX_INSTANCE_NAME_LEN bytes
+    NameTooLong { length: usize },
+    /// a string contains a null byte and cannot be passed to C
+    InteriorNul,
+    /// the entry at index of a list contains a null byte, see InteriorNul
+    InteriorNulAt { index: usize },
+    /// input is not the name of any kind variant
+    ParseFailed { kind: &'static str, input: String },
+    /// reading or writing a file failed
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+    /// a mapping manifest is malformed at the 1-based line
+    ManifestInvalid { line: usize, reason: String },
+    /// loading the usvfs DLL failed
+    LoadFailed { path: String, code: u32 },
+    /// logging was initialized before
+    LoggingAlreadyInitialized,
+    /// an operation did not finish before its timeout elapsed
+    TimedOut,
+    /// the process isn't connected to a VFS, or usvfs doesn't know of one
+    NotConnected,
+    /// a Win32 call failed with the code, see UsvfsError::win32()
+    Win32 { code: u32 },
+    /// flags were given that a link of kind doesn't accept, see
+    /// LinkFlags::validate_for()
+    InvalidFlags { flags: LinkFlags, kind: MappingKind },
+}
+
+impl UsvfsError {
+    /// the Win32 error code attached to this error, if any
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            Self::AlreadyExists => Some(ERROR_ALREADY_EXISTS.0),
+            Self::AccessDenied => Some(ERROR_ACCESS_DENIED.0),
+            Self::CreateFailed { code }
+            | Self::ConnectFailed { code }
+            | Self::LinkFailed { code, .. }
+            | Self::DumpFailed { code }
+            | Self::ProcessListFailed { code }
+            | Self::WaitFailed { code, .. }
+            | Self::ResumeFailed { code, .. }
+            | Self::CloseFailed { code }
+            | Self::LoadFailed { code, .. }
+            | Self::Win32 { code }
+            | Self::ProcessSpawnFailed {
+                win32_code: code, ..
+            } => Some(*code),
+            Self::EmptyInstanceName
+            | Self::NameTooLong { .. }
+            | Self::InteriorNul
+            | Self::InteriorNulAt { .. }
+            | Self::ParseFailed { .. }
+            | Self::Io { .. }
+            | Self::ManifestInvalid { .. }
+            | Self::LoggingAlreadyInitialized
+            | Self::TimedOut
+            | Self::NotConnected
+            | Self::InvalidFlags { .. }
+            | Self::NoSuchProcess { .. } => None,
+        }
+    }
+
+    /// the error for a Win32 call that failed with code. The system's
+    /// description of the code is only looked up when it is displayed
+    pub fn win32(code: u32) -> Self {
+        Self::Win32 { code }
+    }
+}
+
+/// the system's description of a Win32 error code, as FormatMessage
+/// returns it for the user's language
+pub fn system_message(code: u32) -> String {
+    HRESULT::from_win32(code).message().trim_end().to_owned()
+}
+
+impl Display for UsvfsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreateFailed { code } => write!(f, "failed to create VFS (win32 error {code})"),
+            Self::AlreadyExists => write!(f, "failed to create VFS, it already exists"),
+            Self::AccessDenied => write!(f, "failed to create VFS, access denied"),
+            Self::ConnectFailed { code } => {
+                write!(f, "failed to connect to VFS (win32 error {code})")
+            }
+            Self::LinkFailed {
+                source,
+                destination,
+                code,
+            } => write!(
+                f,
+                "failed to link {source} to {destination} (win32 error {code})"
+            ),
+            Self::ProcessSpawnFailed {
+                application_name,
+                win32_code,
+            } => write!(
+                f,
+                "failed to create hooked process {application_name}: {} (win32 error {win32_code})",
+                system_message(*win32_code)
+            ),
+            Self::DumpFailed { code } => write!(f, "failed to dump VFS (win32 error {code})"),
+            Self::ProcessListFailed { code } => {
+                write!(f, "failed to list hooked processes (win32 error {code})")
+            }
+            Self::WaitFailed { pid, code } => {
+                write!(f, "failed to wait for process {pid} (win32 error {code})")
+            }
+            Self::NoSuchProcess { pid } => {
+                write!(f, "no process with id {pid}, it may have exited already")
+            }
+            Self::ResumeFailed { pid, code } => {
+                write!(f, "failed to resume process {pid} (win32 error {code})")
+            }
+            Self::CloseFailed { code } => write!(f, "failed to close handle (win32 error {code})"),
+            Self::EmptyInstanceName => write!(f, "instance name must not be empty"),
+            Self::NameTooLong { length } => write!(
+                f,
+                "instance name is {length} bytes long, at most {} are allowed",
+                crate::MAX_INSTANCE_NAME_LEN
+            ),
+            Self::InteriorNul => write!(f, "string contains an interior null byte"),
+            Self::InteriorNulAt { index } => {
+                write!(f, "entry {index} contains an interior null byte")
+            }
+            Self::ParseFailed { kind, input } => write!(f, "unknown {kind} \"{input}\""),
+            Self::Io { message, .. } => write!(f, "{message}"),
+            Self::ManifestInvalid { line, reason } => {
+                write!(f, "invalid manifest at line {line}: {reason}")
+            }
+            Self::LoadFailed { path, code } => {
+                write!(f, "failed to load {path} (win32 error {code})")
+            }
+            Self::LoggingAlreadyInitialized => write!(f, "logging is already initialized"),
+            Self::TimedOut => write!(f, "operation timed out"),
+            Self::NotConnected => write!(f, "not connected to a VFS"),
+            Self::Win32 { code } => write!(f, "{} (win32 error {code})", system_message(*code)),
+            Self::InvalidFlags { flags, kind } => {
+                let kind = match kind {
+                    MappingKind::File => "file",
+                    MappingKind::DirectoryStatic => "directory",
+                };
+                write!(f, "link flags {flags:?} can't be used for a {kind} link")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UsvfsError {}
+
+impl From<io::Error> for UsvfsError {
+    fn from(error: io::Error) -> Self {
+        Self::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<windows::core::Error> for UsvfsError {
+    /// errors of Win32 calls carry their code as an HRESULT of the Win32
+    /// facility, those are unwrapped again. Any other HRESULT is kept whole
+    fn from(error: windows::core::Error) -> Self {
+        Self::win32(error_code(&error))
+    }
+}
+
+/// the Win32 error code of error, or its whole HRESULT if it isn't one of
+/// the Win32 facility, see From<windows::core::Error>
+pub(crate) fn error_code(error: &windows::core::Error) -> u32 {
+    let hresult = error.code().0 as u32;
+    match hresult & 0xFFFF_0000 {
+        0x8007_0000 => hresult & 0xFFFF,
+        _ => hresult,
+    }
+}
+
+/// the error for usvfsCreateVFS failing with the Win32 error code
+pub(crate) fn create_error(code: u32) -> UsvfsError {
+    match WIN32_ERROR(code) {
+        ERROR_ALREADY_EXISTS => UsvfsError::AlreadyExists,
+        ERROR_ACCESS_DENIED => UsvfsError::AccessDenied,
+        _ => UsvfsError::CreateFailed { code },
+    }
+}
+
+/// get the calling thread's last Win32 error code
+pub(crate) fn last_error() -> u32 {
+    unsafe { GetLastError().0 }
+}
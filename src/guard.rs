@@ -0,0 +1,157 @@
+use std::{io, path::Path};
+
+use crate::{
+    add_skip_directory, blacklist_executable, blacklisted_executables, clear_executable_blacklist,
+    clear_library_force_loads, clear_skip_directories, force_load_library, forced_libraries,
+    skipped_directories, LogLevel, ParametersHandle, UsvfsError,
+};
+
+/// Blacklists executables for as long as the guard is alive.
+///
+/// usvfs can only clear the whole blacklist, not remove single entries, so
+/// dropping the guard clears it and then re-adds whatever was blacklisted
+/// through this crate before the guard was created, see blacklisted_executables().
+/// Entries other controllers of the same VFS added in the meantime are lost.
+pub struct BlacklistGuard {
+    previous: Vec<String>,
+}
+
+impl BlacklistGuard {
+    /// add names to the executable blacklist until the guard is dropped
+    pub fn new(names: &[&str]) -> Self {
+        let previous = blacklisted_executables();
+        for name in names {
+            blacklist_executable(name);
+        }
+        Self { previous }
+    }
+
+    /// the blacklist entries that will be restored when the guard is dropped
+    pub fn previous(&self) -> &[String] {
+        &self.previous
+    }
+}
+
+impl Drop for BlacklistGuard {
+    fn drop(&mut self) {
+        clear_executable_blacklist();
+        for name in &self.previous {
+            blacklist_executable(name);
+        }
+    }
+}
+
+/// Skips directory names during directory linking for as long as the guard
+/// is alive, eg to leave out .git for a single static directory link.
+///
+/// The skip-list is global to the whole VFS, not to this guard, so every
+/// link made while it is alive skips these names, from any thread. Using
+/// guards from several threads at once needs external synchronization, since
+/// they restore over each other. Like BlacklistGuard, dropping it clears the
+/// skip-list and re-adds what was added through this crate before.
+pub struct SkipDirsGuard {
+    previous: Vec<String>,
+}
+
+impl SkipDirsGuard {
+    /// add directory names to the skip-list until the guard is dropped
+    pub fn new(directories: &[&str]) -> Self {
+        let previous = skipped_directories();
+        for directory in directories {
+            add_skip_directory(directory);
+        }
+        Self { previous }
+    }
+
+    /// the skip-list entries that will be restored when the guard is dropped
+    pub fn previous(&self) -> &[String] {
+        &self.previous
+    }
+}
+
+impl Drop for SkipDirsGuard {
+    fn drop(&mut self) {
+        clear_skip_directories();
+        for directory in &self.previous {
+            add_skip_directory(directory);
+        }
+    }
+}
+
+/// Force loads libraries into hooked processes for as long as the guard is
+/// alive, eg to inject a logging DLL into a single launch. Like BlacklistGuard,
+/// dropping it clears the force loads and re-adds what was registered through
+/// this crate before, see forced_libraries().
+pub struct ForceLoadGuard {
+    previous: Vec<(String, String)>,
+}
+
+impl ForceLoadGuard {
+    /// force load each library into processes with the paired name until the
+    /// guard is dropped. Fails without registering anything if one of the
+    /// libraries doesn't exist
+    pub fn new(libraries: &[(&str, &str)]) -> Result<Self, UsvfsError> {
+        if let Some((_, missing)) = libraries
+            .iter()
+            .find(|(_, library_path)| !Path::new(library_path).is_file())
+        {
+            return Err(UsvfsError::Io {
+                kind: io::ErrorKind::NotFound,
+                message: format!("{missing}: library not found"),
+            });
+        }
+
+        let previous = forced_libraries();
+        for (process_name, library_path) in libraries {
+            force_load_library(process_name, library_path);
+        }
+        Ok(Self { previous })
+    }
+
+    /// the force loads that will be restored when the guard is dropped
+    pub fn previous(&self) -> &[(String, String)] {
+        &self.previous
+    }
+}
+
+impl Drop for ForceLoadGuard {
+    fn drop(&mut self) {
+        clear_library_force_loads();
+        for (process_name, library_path) in &self.previous {
+            force_load_library(process_name, library_path);
+        }
+    }
+}
+
+/// Sets the log level of the connected VFS for as long as the guard is
+/// alive, eg to log a bug at Debug while reproducing it.
+///
+/// The level is set on params and pushed with ParametersHandle::update(),
+/// dropping the guard sets the level params had before and pushes that.
+/// It is set even if it is less verbose than the previous one
+pub struct LogLevelGuard<'a> {
+    params: &'a mut ParametersHandle,
+    previous: LogLevel,
+}
+
+impl<'a> LogLevelGuard<'a> {
+    /// switch the VFS to level until the guard is dropped
+    pub fn raise(params: &'a mut ParametersHandle, level: LogLevel) -> Self {
+        let previous = params.log_level();
+        params.set_log_level(level);
+        params.update();
+        Self { params, previous }
+    }
+
+    /// the level that will be restored when the guard is dropped
+    pub fn previous(&self) -> LogLevel {
+        self.previous
+    }
+}
+
+impl Drop for LogLevelGuard<'_> {
+    fn drop(&mut self) {
+        self.params.set_log_level(self.previous);
+        self.params.update();
+    }
+}
@@ -0,0 +1,161 @@
+//! End to end tests that hook a real process into a VFS and check what it
+//! sees. Unlike the tests in lib.rs these need everything usvfs needs to
+//! inject its hooks: usvfs_x64.dll, usvfs_x86.dll, usvfs_proxy_x64.exe and
+//! usvfs_proxy_x86.exe have to be next to the test binary in target\debug\deps
+//! or on the PATH. They are ignored by default so CI without them still
+//! passes, run them locally with
+//!
+//! ```text
+//! cargo test -- --ignored
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::MutexGuard,
+};
+
+use crate::{
+    generate_instance_name, run_in_vfs, HookedCommand, LinkFlags, MappingKind, MappingRecord, Vfs,
+    VfsConfig,
+};
+
+use crate::tests::{test_lock, test_vfs};
+
+/// A VFS with a temp directory to link from and into. The directory holds a
+/// `source` directory for the real files and a `game` directory to link them
+/// into, and is removed again on drop. Holds the test lock of lib.rs until
+/// then, after the VFS is gone
+struct Harness {
+    dir: PathBuf,
+    vfs: Vfs,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Harness {
+    /// a fresh temp directory and VFS for the test called name
+    fn new(name: &str) -> Self {
+        let (lock, vfs) = test_vfs(&generate_instance_name(name));
+        let dir = std::env::temp_dir().join(format!("usvfs-rs-integration-{name}"));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(dir.join("source")).expect("Failed to create source directory");
+        std::fs::create_dir_all(dir.join("game")).expect("Failed to create game directory");
+
+        Self {
+            dir,
+            vfs,
+            _lock: lock,
+        }
+    }
+
+    /// write a real file below the source directory
+    fn source(&self, name: &str, contents: &[u8]) -> PathBuf {
+        let path = self.dir.join("source").join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create source directory");
+        }
+        std::fs::write(&path, contents).expect("Failed to write source file");
+        path
+    }
+
+    /// the path of name below the game directory, where things are linked to
+    fn game(&self, name: &str) -> PathBuf {
+        self.dir.join("game").join(name)
+    }
+
+    /// have a hooked cmd.exe copy virtual_path to a real file and return its
+    /// contents, which is what a hooked process reads at virtual_path
+    fn read_hooked(&self, virtual_path: &Path) -> Vec<u8> {
+        let copy = self.dir.join("copy.bin");
+        std::fs::remove_file(&copy).ok();
+
+        let mut command = HookedCommand::new("C:\\Windows\\System32\\cmd.exe");
+        command
+            .arg("/c")
+            .arg("copy")
+            .arg("/b")
+            .arg("/y")
+            .arg(virtual_path)
+            .arg(&copy);
+        let process = command.spawn().expect("Failed to spawn hooked process");
+        let exit_code = process.wait().expect("Failed to wait for hooked process");
+        assert_eq!(exit_code, 0, "copying {} failed", virtual_path.display());
+
+        std::fs::read(&copy).expect("Failed to read copy")
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+#[test]
+#[ignore = "hooks a real process, see the module documentation"]
+fn hookedProcessReadsLinkedFile() {
+    let mut harness = Harness::new("file");
+    let source = harness.source("plugin.esp", b"linked through usvfs");
+    let destination = harness.game("plugin.esp");
+    harness
+        .vfs
+        .link_file(&source, &destination, LinkFlags::empty())
+        .expect("Failed to link file");
+
+    assert!(!destination.exists());
+    assert_eq!(harness.read_hooked(&destination), b"linked through usvfs");
+}
+
+#[test]
+#[ignore = "hooks a real process, see the module documentation"]
+fn hookedProcessReadsLinkedDirectory() {
+    let mut harness = Harness::new("directory");
+    harness.source("data\\textures\\sky.dds", "ünïcödé".as_bytes());
+    let destination = harness.game("data");
+    std::fs::create_dir_all(&destination).expect("Failed to create destination");
+    harness
+        .vfs
+        .link_directory_static(
+            harness.dir.join("source").join("data"),
+            &destination,
+            LinkFlags::RECURSIVE,
+        )
+        .expect("Failed to link directory");
+
+    let sky = destination.join("textures").join("sky.dds");
+    assert!(!sky.exists());
+    assert_eq!(harness.read_hooked(&sky), "ünïcödé".as_bytes());
+}
+
+#[test]
+#[ignore = "hooks a real process, see the module documentation"]
+fn runInVfsLaunches() {
+    let _lock = test_lock();
+    let dir = std::env::temp_dir().join("usvfs-rs-integration-run");
+    std::fs::create_dir_all(dir.join("game")).expect("Failed to create game directory");
+    let source = dir.join("plugin.esp");
+    std::fs::write(&source, b"launched").expect("Failed to write source file");
+    let destination = dir.join("game").join("plugin.esp");
+    let copy = dir.join("copy.bin");
+
+    let config = VfsConfig {
+        instance_name: generate_instance_name("run"),
+        ..VfsConfig::default()
+    };
+    let mappings = [MappingRecord {
+        source,
+        destination: destination.clone(),
+        flags: LinkFlags::empty(),
+        kind: MappingKind::File,
+    }];
+    let mut command = HookedCommand::new("C:\\Windows\\System32\\cmd.exe");
+    command
+        .args(["/c", "copy", "/b", "/y"])
+        .arg(&destination)
+        .arg(&copy);
+    let exit_code = run_in_vfs(config, &mappings, command);
+    let copied = std::fs::read(&copy);
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(exit_code, Ok(0));
+    assert_eq!(copied.expect("Failed to read copy"), b"launched");
+}
@@ -4,36 +4,169 @@
     non_upper_case_globals,
     dead_code
 )]
-#![feature(arbitrary_self_types_pointers)]
 
 use std::{
-    ffi::{CStr, CString},
+    borrow::Cow,
+    ffi::{CString, OsStr, OsString},
     fmt::{Display, Formatter},
-    ptr, time,
+    fs::File,
+    io::Write,
+    mem,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    os::windows::io::FromRawHandle,
+    path::{Path, PathBuf},
+    ptr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+    time,
 };
 
+use bitflags::bitflags;
 use libc::{c_int, c_void, size_t};
 use windows::Win32::{
+    Foundation::{CloseHandle, ERROR_MORE_DATA, HANDLE, MAX_PATH},
     Security::SECURITY_ATTRIBUTES,
-    System::Threading::{PROCESS_INFORMATION, STARTUPINFOW},
+    System::Threading::{
+        CREATE_SUSPENDED, PROCESS_CREATION_FLAGS, PROCESS_INFORMATION, STARTUPINFOW,
+    },
 };
 
-macro_rules! widen {
-    ( $str:ident ) => {{
-        let mut vector: Vec<u16> = $str.encode_utf16().collect();
+/// encodes a string or path as a null terminated UTF-16 string for the
+/// wide usvfs functions. Paths are encoded from their OsStr, so characters
+/// that aren't valid UTF-8 survive. The returned vector owns the buffer,
+/// so it must be bound to a local that outlives the FFI call it is passed to
+fn widen(s: impl AsRef<OsStr>) -> Vec<u16> {
+    s.as_ref()
+        .encode_wide()
         // push a null terminator
-        vector.push(0x00);
-        vector.as_ptr()
-    }};
+        .chain(std::iter::once(0x00))
+        .collect()
 }
 
-macro_rules! widen_mut {
-    ( $str:ident ) => {{
-        let mut vector: Vec<u16> = $str.encode_utf16().collect();
-        // push a null terminator
-        vector.push(0x00);
-        vector.as_mut_ptr()
-    }};
+/// the longest path the Win32 file functions take without the `\\?\`
+/// prefix, MAX_PATH less the null terminator
+const MAX_UNPREFIXED_PATH: usize = MAX_PATH as usize - 1;
+
+/// encodes a path like widen(), adding the `\\?\` prefix that lifts the
+/// MAX_PATH limit of the Win32 file functions if path is too long for them.
+/// Drive paths become `\\?\C:\...` and UNC paths `\\?\UNC\server\share\...`,
+/// and forward slashes become backslashes since the prefix turns off that
+/// normalization. The prefix is not added to
+///   - paths short enough to work without it
+///   - paths that already start with `\\?\` or `\\.\`, they are kept as they are
+///   - relative paths like `data\plugin.esp` or `C:data`, the prefix only
+///     works for absolute ones
+///   - paths with `.`, `..` or empty components, which Windows no longer
+///     resolves once the prefix is there
+///
+/// Only paths usvfs passes on to the file system are prefixed this way, like
+/// the sources of links. Destinations are keys of the mapping tree, which is
+/// matched against the paths hooked processes use, so they must not be
+fn widen_path(path: &Path) -> Vec<u16> {
+    let wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .map(|unit| match unit == u16::from(b'/') {
+            true => u16::from(b'\\'),
+            false => unit,
+        })
+        .collect();
+    if wide.len() <= MAX_UNPREFIXED_PATH {
+        return widen(path);
+    }
+
+    // only looked at to decide on the prefix, the path is kept as UTF-16
+    let text = String::from_utf16_lossy(&wide);
+    let (prefix, rest) = match text.as_bytes() {
+        [b'\\', b'\\', b'?' | b'.', b'\\', ..] => return widen(path),
+        [b'\\', b'\\', ..] => ("\\\\?\\UNC\\", &wide[2..]),
+        [drive, b':', b'\\', ..] if drive.is_ascii_alphabetic() => ("\\\\?\\", &wide[..]),
+        _ => return widen(path),
+    };
+    let unresolved = String::from_utf16_lossy(rest)
+        .trim_end_matches('\\')
+        .split('\\')
+        .any(|component| matches!(component, "" | "." | ".."));
+    if unresolved {
+        return widen(path);
+    }
+    prefix
+        .encode_utf16()
+        .chain(rest.iter().copied())
+        .chain(std::iter::once(0x00))
+        .collect()
+}
+
+/// path the way widen_path() passes it to usvfs, and so the way the link
+/// functions record it as the source of a mapping
+fn prefixed_path(path: &Path) -> PathBuf {
+    let wide = widen_path(path);
+    PathBuf::from(OsString::from_wide(&wide[..wide.len() - 1]))
+}
+
+mod capabilities;
+mod command;
+mod config;
+mod dump;
+mod env;
+mod error;
+mod guard;
+#[cfg(test)]
+mod integration;
+#[cfg(feature = "delay-load")]
+mod load;
+mod log;
+mod manifest;
+mod process;
+mod rules;
+mod temp;
+mod timeout;
+mod vfs;
+
+pub use capabilities::{capabilities, usvfs_version, Capabilities};
+pub use command::HookedCommand;
+pub use config::VfsConfig;
+pub use dump::{
+    count_dump_nodes, parse_vfs_dump, DumpStats, NodeKind, ParseError, VfsNode, VfsTree,
+};
+pub use env::EnvBlock;
+use error::{create_error, last_error};
+pub use error::{system_message, UsvfsError};
+pub use guard::{BlacklistGuard, ForceLoadGuard, LogLevelGuard, SkipDirsGuard};
+#[cfg(feature = "delay-load")]
+pub use load::Usvfs;
+#[cfg(feature = "tracing")]
+pub use log::pump_into_tracing;
+pub use log::{next_log_message, next_log_message_timeout, spawn_log_pump, LogMessage};
+pub use manifest::{apply_manifest, parse_manifest, ManifestEntry};
+pub use process::{
+    close_process_information, vfs_process_infos, wait_for_all_processes, wait_for_pid,
+    HookedProcess, ProcessInfo,
+};
+pub use rules::GlobalRules;
+pub use temp::{link_bytes, LinkedTempFile};
+pub use timeout::{
+    vfs_dump_timeout, virtually_link_directory_static_timeout, virtually_link_file_timeout,
+    with_timeout,
+};
+pub use vfs::{run_in_vfs, with_vfs, DryRunVfs, Vfs};
+
+/// decodes a null terminated narrow string written by usvfs into
+/// buffer, everything after the first null is ignored.
+///
+/// usvfs works in UTF-16 internally but converts to UTF-8 before it writes
+/// names, log messages and dumps into char buffers, regardless of the
+/// system locale. Bytes that aren't valid UTF-8, e.g. a message cut in the
+/// middle of a character, are replaced with U+FFFD instead of failing
+fn narrow_to_string(buffer: &[u8]) -> String {
+    let length = buffer
+        .iter()
+        .position(|&byte| byte == 0x00)
+        .unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..length]).into_owned()
 }
 
 // USVFS Bindings
@@ -63,76 +196,422 @@ pub const LINKFLAG_RECURSIVE: u32 = 0x00000008;
 /// the sharedparameters class, those lists are checked during virtual linking
 pub const LINKFLAG_FAILIFSKIPPED: u32 = 0x00000010;
 
+bitflags! {
+    /// Typed set of the LINKFLAG_* constants accepted by the linking
+    /// functions. Use bits() or from_bits_truncate() to convert to and
+    /// from the raw integer flags
+    ///
+    /// usvfs has no copy-on-write mode, writes to a linked file always change
+    /// the real source file. CREATE_TARGET only redirects files that are newly
+    /// created below the destination, so it can collect new files like configs
+    /// a program writes in a separate directory, but not copies of modified ones
+    ///
+    /// Not every flag makes sense for every link, see validate_for():
+    ///   - file links accept FAIL_IF_EXISTS and FAIL_IF_SKIPPED
+    ///   - static directory links accept every flag, MONITOR_CHANGES,
+    ///     CREATE_TARGET and RECURSIVE only apply to them
+    ///
+    /// The link functions reject any other combination, as well as bits
+    /// without a flag, with InvalidFlags before calling usvfs
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct LinkFlags: u32 {
+        /// see LINKFLAG_FAILIFEXISTS
+        const FAIL_IF_EXISTS = LINKFLAG_FAILIFEXISTS;
+        /// see LINKFLAG_MONITORCHANGES
+        const MONITOR_CHANGES = LINKFLAG_MONITORCHANGES;
+        /// see LINKFLAG_CREATETARGET
+        const CREATE_TARGET = LINKFLAG_CREATETARGET;
+        /// see LINKFLAG_RECURSIVE
+        const RECURSIVE = LINKFLAG_RECURSIVE;
+        /// see LINKFLAG_FAILIFSKIPPED
+        const FAIL_IF_SKIPPED = LINKFLAG_FAILIFSKIPPED;
+    }
+}
+
+impl LinkFlags {
+    /// the flags a file link accepts, the others only apply to directories
+    pub const FILE_FLAGS: Self = Self::FAIL_IF_EXISTS.union(Self::FAIL_IF_SKIPPED);
+
+    /// the flags a link of kind accepts
+    pub fn allowed_for(kind: MappingKind) -> Self {
+        match kind {
+            MappingKind::File => Self::FILE_FLAGS,
+            MappingKind::DirectoryStatic => Self::all(),
+        }
+    }
+
+    /// check these flags can be used for a link of kind, returning
+    /// InvalidFlags with the offending flags otherwise
+    pub fn validate_for(self, kind: MappingKind) -> Result<(), UsvfsError> {
+        let invalid = Self::from_bits_retain(self.bits() & !Self::allowed_for(kind).bits());
+        match invalid.is_empty() {
+            true => Ok(()),
+            false => Err(UsvfsError::InvalidFlags {
+                flags: invalid,
+                kind,
+            }),
+        }
+    }
+}
+
 /// Opaque type for usvfsParameters
 /// This type stores information about the VFS to be
-/// created. To create a VFS, create a new Parameters
-/// and run the set functions on it to set properties
-/// since the struct is opaque, it can only be interacted
-/// with by these functions. The function free_parameters()
-/// **MUST** be run after use since we must tell the C++
-/// library to deallocate it which cannot be handled from Rust
+/// created. Since the struct is opaque, it can only be
+/// interacted with through a pointer, see ParametersHandle
+/// for the safe owning wrapper around it
 #[repr(C)]
 pub struct Parameters {
     _data: [u8; 0],
     _marker: core::marker::PhantomData<(*mut u8, core::marker::PhantomPinned)>,
 }
 
-impl Parameters {
-    /// Creates a new Parameters
-    pub fn new() -> *mut Self {
-        unsafe { usvfsCreateParameters() }
+/// Owning handle to a usvfsParameters allocated by the C++ library.
+/// To create a VFS, create a new ParametersHandle and run the set
+/// functions on it to set properties. The parameters are freed by
+/// the library when the handle is dropped, so only drop it after
+/// closing any associated VFSs
+///
+/// There is no parameter for the size of the shared memory the VFS lives
+/// in. usvfs starts the mapping tree and the sharedparameters in small
+/// segments and moves them to a larger one whenever they run full, so
+/// large trees need no configuration. A link that fails anyway returns
+/// LinkFailed with the Win32 error usvfs left behind
+///
+/// There is no parameter for case sensitivity either. usvfs always matches
+/// virtual paths without regard to case, like Windows does, so a file linked
+/// as `data\plugin.esp` is found as `Data\Plugin.ESP` too and the link
+/// functions don't need to normalize anything. The dump may show names in a
+/// different case than they were linked with, resolve() and VfsTree::find()
+/// ignore case the same way
+pub struct ParametersHandle {
+    raw: *mut Parameters,
+    /// the values last set, usvfs has no getters for them
+    shadow: ParametersShadow,
+}
+
+/// Copy of the values set on a ParametersHandle, as usvfs stores them
+#[derive(Debug, Clone)]
+struct ParametersShadow {
+    instance_name: String,
+    debug_mode: bool,
+    log_level: LogLevel,
+    crash_dumps_type: CrashDumpsType,
+    crash_dumps_path: PathBuf,
+    process_delay: time::Duration,
+}
+
+impl Default for ParametersShadow {
+    /// what usvfsCreateParameters() starts out with
+    fn default() -> Self {
+        Self {
+            instance_name: String::new(),
+            debug_mode: false,
+            log_level: LogLevel::Debug,
+            crash_dumps_type: CrashDumpsType::Nil,
+            crash_dumps_path: PathBuf::new(),
+            process_delay: time::Duration::ZERO,
+        }
     }
+}
 
-    /// set the name for the VFS instance
-    pub fn set_instance_name(self: *mut Parameters, name: &str) {
+impl ParametersHandle {
+    /// Creates a new ParametersHandle
+    pub fn new() -> Self {
+        unsafe {
+            Self {
+                raw: usvfsCreateParameters(),
+                shadow: ParametersShadow::default(),
+            }
+        }
+    }
+
+    /// Creates a ParametersBuilder to configure a ParametersHandle in one expression
+    pub fn builder() -> ParametersBuilder {
+        ParametersBuilder::new()
+    }
+
+    /// Creates an independent copy of these parameters. The copy is
+    /// a separate allocation that is freed on its own when dropped
+    pub fn duplicate(&self) -> Self {
         unsafe {
-            let cName = CString::new(name).expect("Invalid C-String");
-            usvfsSetInstanceName(self, cName.as_ptr())
+            Self {
+                raw: usvfsDupeParameters(self.raw),
+                shadow: self.shadow.clone(),
+            }
+        }
+    }
+
+    /// overwrite all of dest's parameters with these
+    pub fn copy_into(&self, dest: &mut ParametersHandle) {
+        unsafe { usvfsCopyParameters(self.raw, dest.raw) };
+        dest.shadow.clone_from(&self.shadow);
+    }
+
+    /// get the raw pointer to the underlying parameters. The pointer
+    /// is only valid for as long as the handle is alive
+    pub fn as_ptr(&self) -> *const Parameters {
+        self.raw
+    }
+
+    /// get the raw mutable pointer to the underlying parameters. The pointer
+    /// is only valid for as long as the handle is alive. Changes made through
+    /// it aren't seen by the getters of the handle
+    pub fn as_mut_ptr(&mut self) -> *mut Parameters {
+        self.raw
+    }
+
+    /// set the name for the VFS instance. Fails if the name
+    /// contains a null byte or is longer than MAX_INSTANCE_NAME_LEN
+    pub fn set_instance_name(&mut self, name: &str) -> Result<(), UsvfsError> {
+        if name.len() > MAX_INSTANCE_NAME_LEN {
+            return Err(UsvfsError::NameTooLong { length: name.len() });
         }
+        let cName = CString::new(name).map_err(|_| UsvfsError::InteriorNul)?;
+        unsafe { usvfsSetInstanceName(self.raw, cName.as_ptr()) };
+        self.shadow.instance_name = name.to_owned();
+        Ok(())
+    }
+
+    /// the instance name last set, empty if none was. The getters all track
+    /// the values on the handle since the parameters can't be read back, so
+    /// they start out at the usvfs defaults
+    pub fn instance_name(&self) -> &str {
+        &self.shadow.instance_name
+    }
+
+    /// set whether the VFS should output debug information
+    pub fn set_debug_mode(&mut self, debug_mode: bool) {
+        unsafe { usvfsSetDebugMode(self.raw, debug_mode) };
+        self.shadow.debug_mode = debug_mode;
+    }
+
+    /// whether debug mode was last set, false by default
+    pub fn debug_mode(&self) -> bool {
+        self.shadow.debug_mode
+    }
+
+    /// set the VFS log level
+    pub fn set_log_level(&mut self, log_level: LogLevel) {
+        unsafe { usvfsSetLogLevel(self.raw, log_level) };
+        self.shadow.log_level = log_level;
+    }
+
+    /// the log level last set, Debug by default
+    pub fn log_level(&self) -> LogLevel {
+        self.shadow.log_level
+    }
+
+    /// set the VFS crash dumps type
+    pub fn set_crash_dumps_type(&mut self, dump_type: CrashDumpsType) {
+        unsafe { usvfsSetCrashDumpType(self.raw, dump_type) };
+        self.shadow.crash_dumps_type = dump_type;
+    }
+
+    /// the crash dumps type last set, Nil by default
+    pub fn crash_dumps_type(&self) -> CrashDumpsType {
+        self.shadow.crash_dumps_type
+    }
+
+    /// set the path for crash dumps. An empty path "" dumps to
+    /// the current working directory. usvfs only takes a narrow
+    /// string here, so characters that aren't valid UTF-8 are replaced.
+    /// Fails if the path contains a null byte
+    pub fn set_crash_dumps_path(&mut self, path: impl AsRef<Path>) -> Result<(), UsvfsError> {
+        let path = path.as_ref().to_string_lossy();
+        let cPath = CString::new(path.as_bytes()).map_err(|_| UsvfsError::InteriorNul)?;
+        unsafe { usvfsSetCrashDumpPath(self.raw, cPath.as_ptr()) };
+        self.shadow.crash_dumps_path = PathBuf::from(path.into_owned());
+        Ok(())
+    }
+
+    /// the crash dumps path last set, with the replacements usvfs got,
+    /// empty by default
+    pub fn crash_dumps_path(&self) -> &Path {
+        &self.shadow.crash_dumps_path
+    }
+
+    /// set the amount of time to delay the process. usvfs stores the
+    /// delay as milliseconds in an i32 and has no wider parameter, so
+    /// anything longer than MAX_PROCESS_DELAY (about 24.8 days) is capped
+    /// to it rather than wrapping around. Check process_delay() afterwards
+    /// to tell whether the delay was capped
+    pub fn set_process_delay(&mut self, time: time::Duration) {
+        let millis = process_delay_millis(time);
+        unsafe { usvfsSetProcessDelay(self.raw, millis) };
+        self.shadow.process_delay = time::Duration::from_millis(millis as u64);
+    }
+
+    /// the process delay last set as usvfs stores it, cut to whole
+    /// milliseconds and capped to MAX_PROCESS_DELAY, zero by default
+    pub fn process_delay(&self) -> time::Duration {
+        self.shadow.process_delay
+    }
+
+    /// push these parameters to the VFS that is currently connected,
+    /// without having to recreate it
+    ///
+    /// Only debug mode, log level, crash dumps type, crash dumps path and
+    /// process delay are applied at runtime. The instance name can't be
+    /// changed on a live VFS, that requires disconnecting and creating or
+    /// connecting again with the new parameters
+    pub fn update(&mut self) {
+        unsafe { usvfsUpdateParameters(self.raw) }
+    }
+}
+
+impl Default for ParametersHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for ParametersHandle {
+    /// see duplicate()
+    fn clone(&self) -> Self {
+        self.duplicate()
+    }
+
+    /// see copy_into(), reuses the existing allocation
+    fn clone_from(&mut self, source: &Self) {
+        source.copy_into(self)
+    }
+}
+
+impl Drop for ParametersHandle {
+    fn drop(&mut self) {
+        unsafe { usvfsFreeParameters(self.raw) }
+    }
+}
+
+// The parameters are a plain heap allocation that usvfs doesn't tie to the
+// thread that created it, so the handle can be moved to and freed on another
+// thread. The setters write to it without any locking though, so it is not Sync
+unsafe impl Send for ParametersHandle {}
+
+/// longest instance name usvfs can store, in bytes of its UTF-8 encoding
+/// rather than UTF-16 units since the name is passed as a narrow string.
+/// usvfs keeps it in a 65 byte array including the terminator and would
+/// silently cut longer names, which then no longer match the shared memory
+/// other processes look for
+pub const MAX_INSTANCE_NAME_LEN: usize = 64;
+
+/// how many names generate_instance_name() handed out in this process
+static GENERATED_INSTANCE_NAMES: AtomicU32 = AtomicU32::new(0);
+
+/// a new instance name starting with prefix, followed by the process id,
+/// the current time in seconds and a count of the names generated so far,
+/// like `prefix-1234-6710b3c2-0`. Names from the same process differ by the
+/// count, the process id keeps runs that are alive at the same time apart,
+/// and the time separates a later run that got the same process id from the
+/// shared memory a crashed one left behind. prefix is cut to keep the name
+/// within MAX_INSTANCE_NAME_LEN
+pub fn generate_instance_name(prefix: &str) -> String {
+    let seconds = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let suffix = format!(
+        "-{}-{:08x}-{}",
+        std::process::id(),
+        // the low bits change fastest, the high ones can be dropped
+        seconds as u32,
+        GENERATED_INSTANCE_NAMES.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let mut end = prefix.len().min(MAX_INSTANCE_NAME_LEN - suffix.len());
+    while !prefix.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{suffix}", &prefix[..end])
+}
+
+/// longest process delay usvfs can store, longer delays are capped to this
+pub const MAX_PROCESS_DELAY: time::Duration = time::Duration::from_millis(c_int::MAX as u64);
+
+/// converts a process delay to the milliseconds usvfs takes, saturating at MAX_PROCESS_DELAY
+fn process_delay_millis(time: time::Duration) -> c_int {
+    time.as_millis().try_into().unwrap_or(c_int::MAX)
+}
+
+/// Chainable builder for a ParametersHandle, so a VFS configuration
+/// can be written as a single expression. Anything not set is left
+/// at the usvfs default, except the instance name which is required
+#[derive(Debug, Clone, Default)]
+pub struct ParametersBuilder {
+    instance_name: String,
+    debug_mode: Option<bool>,
+    log_level: Option<LogLevel>,
+    crash_dumps_type: Option<CrashDumpsType>,
+    crash_dumps_path: Option<PathBuf>,
+    process_delay: Option<time::Duration>,
+}
+
+impl ParametersBuilder {
+    /// Creates a new ParametersBuilder with nothing set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// set the name for the VFS instance
+    pub fn instance_name(mut self, name: &str) -> Self {
+        self.instance_name = name.to_owned();
+        self
     }
 
     /// set whether the VFS should output debug information
-    pub fn set_debug_mode(self: *mut Parameters, debug_mode: bool) {
-        unsafe { usvfsSetDebugMode(self, debug_mode) }
+    pub fn debug_mode(mut self, debug_mode: bool) -> Self {
+        self.debug_mode = Some(debug_mode);
+        self
     }
 
     /// set the VFS log level
-    pub fn set_log_level(self: *mut Parameters, log_level: LogLevel) {
-        unsafe { usvfsSetLogLevel(self, log_level) }
+    pub fn log_level(mut self, log_level: LogLevel) -> Self {
+        self.log_level = Some(log_level);
+        self
     }
 
     /// set the VFS crash dumps type
-    pub fn set_crash_dumps_type(self: *mut Parameters, dump_type: CrashDumpsType) {
-        unsafe { usvfsSetCrashDumpType(self, dump_type) }
+    pub fn crash_dumps_type(mut self, dump_type: CrashDumpsType) -> Self {
+        self.crash_dumps_type = Some(dump_type);
+        self
     }
 
     /// set the path for crash dumps. An empty string "" dumps to
     /// the current working directory
-    pub fn set_crash_dumps_path(self: *mut Parameters, path: &str) {
-        unsafe {
-            let cPath = CString::new(path).expect("Invalid C-String");
-            usvfsSetCrashDumpPath(self, cPath.as_ptr())
-        }
+    pub fn crash_dumps_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.crash_dumps_path = Some(path.as_ref().to_owned());
+        self
     }
 
     /// set the amount of time to delay the process
-    pub fn set_process_delay(self: *mut Parameters, time: time::Duration) {
-        unsafe {
-            usvfsSetProcessDelay(
-                self,
-                time.as_millis()
-                    .try_into()
-                    .expect("Failed to convert time to milliseconds"),
-            )
-        };
+    pub fn process_delay(mut self, time: time::Duration) -> Self {
+        self.process_delay = Some(time);
+        self
     }
 
-    /// free the parameter's memory in C. Not calling this is a memory leak!!!
-    /// Rust's automatic destructor: the drop trait cannot be used on memory that
-    /// Rust does not own (ie only has a pointer to), so this is REQUIRED
-    /// only free parameters after closing any associated VFSs
-    pub fn free_parameters(self: *mut Parameters) {
-        unsafe { usvfsFreeParameters(self) }
+    /// validate the configuration and create the ParametersHandle from it
+    pub fn build(self) -> Result<ParametersHandle, UsvfsError> {
+        if self.instance_name.is_empty() {
+            return Err(UsvfsError::EmptyInstanceName);
+        }
+
+        let mut params = ParametersHandle::new();
+        params.set_instance_name(&self.instance_name)?;
+        if let Some(debug_mode) = self.debug_mode {
+            params.set_debug_mode(debug_mode);
+        }
+        if let Some(log_level) = self.log_level {
+            params.set_log_level(log_level);
+        }
+        if let Some(dump_type) = self.crash_dumps_type {
+            params.set_crash_dumps_type(dump_type);
+        }
+        if let Some(path) = &self.crash_dumps_path {
+            params.set_crash_dumps_path(path)?;
+        }
+        if let Some(time) = self.process_delay {
+            params.set_process_delay(time);
+        }
+        Ok(params)
     }
 }
 
@@ -145,11 +624,54 @@ impl Parameters {
 ///
 /// Please note that you can only be connected to one vfs, so this will silently disconnect
 /// from a previous vfs.
-pub fn create_vfs(params: *const Parameters) -> Result<(), ()> {
+///
+/// Fails with AlreadyExists or AccessDenied when usvfs reports those, and
+/// with CreateFailed carrying the Win32 error code otherwise
+pub fn create_vfs(params: &ParametersHandle) -> Result<(), UsvfsError> {
     unsafe {
-        match usvfsCreateVFS(params) {
-            true => Ok(()),
-            false => Err(()),
+        match usvfsCreateVFS(params.as_ptr()) {
+            true => {
+                // the new vfs starts out without any mappings
+                lock_rules(&MAPPINGS).clear();
+                set_connection(current_vfs_name());
+                Ok(())
+            }
+            false => {
+                let code = last_error();
+                set_connection(None);
+                Err(create_error(code))
+            }
+        }
+    }
+}
+
+/// create_vfs(), retried after delay when it fails, eg because the shared
+/// memory of a VFS that was just torn down is still being released. It is
+/// tried attempts times in total, but at least once, and the error of the
+/// last attempt is returned if none succeeds. With the tracing feature each
+/// retry is logged at debug level
+pub fn create_vfs_retry(
+    params: &ParametersHandle,
+    attempts: u32,
+    delay: time::Duration,
+) -> Result<(), UsvfsError> {
+    let mut attempt = 1;
+    loop {
+        match create_vfs(params) {
+            Err(error) if attempt < attempts => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    target: "usvfs",
+                    attempt,
+                    attempts,
+                    "creating the VFS failed, retrying: {error}"
+                );
+                #[cfg(not(feature = "tracing"))]
+                let _ = error;
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
         }
     }
 }
@@ -158,11 +680,18 @@ pub fn create_vfs(params: *const Parameters) -> Result<(), ()> {
 ///
 /// Please note that you can only be connected to one vfs, so this will silently disconnect
 /// from a previous vfs.
-pub fn connect_vfs(params: *const Parameters) -> Result<(), ()> {
+pub fn connect_vfs(params: &ParametersHandle) -> Result<(), UsvfsError> {
     unsafe {
-        match usvfsConnectVfs(params) {
-            true => Ok(()),
-            false => Err(()),
+        match usvfsConnectVFS(params.as_ptr()) {
+            true => {
+                set_connection(current_vfs_name());
+                Ok(())
+            }
+            false => {
+                let code = last_error();
+                set_connection(None);
+                Err(UsvfsError::ConnectFailed { code })
+            }
         }
     }
 }
@@ -170,11 +699,185 @@ pub fn connect_vfs(params: *const Parameters) -> Result<(), ()> {
 /// disconnect from a virtual filesystem. This removes hooks if necessary
 pub fn disconnect_vfs() {
     unsafe { usvfsDisconnectVFS() }
+    set_connection(None);
+}
+
+/// disconnect like disconnect_vfs(), but only if this process is connected
+/// as far as is_connected() knows. Returns whether it disconnected, so
+/// cleanup code can call it any number of times
+pub fn disconnect_if_connected() -> bool {
+    let mut connection = lock_rules(&CONNECTION);
+    if connection.take().is_none() {
+        return false;
+    }
+    unsafe { usvfsDisconnectVFS() };
+    mappings_changed();
+    true
+}
+
+/// whether install_disconnect_on_panic() installed its panic hook
+static DISCONNECT_ON_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// install a panic hook that disconnects from the vfs with
+/// disconnect_if_connected(), so a panic deep in unrelated code is less
+/// likely to leave the vfs's shared memory behind. The hook that was set
+/// before, e.g. the default one printing the message, is called right
+/// after, so this composes with hooks installed earlier. Returns false
+/// without installing it again if it is installed already
+///
+/// This is best-effort only: the hook runs for every panic on any thread,
+/// including ones that are caught later, and it doesn't run at all if the
+/// process is killed or aborts without panicking. Hooks set after this
+/// call replace it unless they call the previous hook themselves
+pub fn install_disconnect_on_panic() -> bool {
+    if DISCONNECT_ON_PANIC.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| disconnect_then(|| previous(info))));
+    true
+}
+
+/// the body of the hook install_disconnect_on_panic() installs, with
+/// previous calling the hook it replaced
+fn disconnect_then(previous: impl FnOnce()) {
+    disconnect_if_connected();
+    previous();
+}
+
+/// the instance name of the vfs this process is connected to, None while
+/// disconnected. Kept by create_vfs(), connect_vfs() and disconnect_vfs(),
+/// the only ways this crate talks to usvfs about connections
+static CONNECTION: Mutex<Option<String>> = Mutex::new(None);
+
+fn set_connection(instance_name: Option<String>) {
+    *lock_rules(&CONNECTION) = instance_name;
+    mappings_changed();
+}
+
+/// whether this process is connected to a vfs, as far as this crate knows.
+/// Creating or connecting while connected replaces the old connection, a
+/// failed attempt leaves the process disconnected
+pub fn is_connected() -> bool {
+    lock_rules(&CONNECTION).is_some()
+}
+
+/// the instance name of the vfs this process is connected to, as usvfs
+/// reported it when connecting, see is_connected()
+pub fn connected_instance_name() -> Option<String> {
+    lock_rules(&CONNECTION).clone()
+}
+
+/// cheaply check that the vfs this process is connected to still responds,
+/// e.g. to decide whether to recreate it before spawning a process.
+///
+/// usvfs has no call made for this, so healthy means what can be checked
+/// through the calls it has: this crate holds a connection, usvfs reports
+/// an instance name for it, and a dump of the mapping tree can be sized,
+/// which reads the shared memory of the vfs without copying anything. It
+/// doesn't say that hooked processes are still served correctly
+pub fn health_check() -> Result<(), UsvfsError> {
+    if !is_connected() || current_vfs_name().is_none() {
+        return Err(UsvfsError::NotConnected);
+    }
+    vfs_dump_size().map(|_| ())
+}
+
+/// Which link function created a mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingKind {
+    /// made by virtually_link_file()
+    File,
+    /// made by virtually_link_directory_static()
+    DirectoryStatic,
+}
+
+/// A successful link, as recorded by this crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingRecord {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub flags: LinkFlags,
+    pub kind: MappingKind,
+}
+
+impl MappingRecord {
+    /// link this mapping again with the function that originally made it
+    pub fn apply(&self) -> Result<(), UsvfsError> {
+        match self.kind {
+            MappingKind::File => virtually_link_file(&self.source, &self.destination, self.flags),
+            MappingKind::DirectoryStatic => {
+                virtually_link_directory_static(&self.source, &self.destination, self.flags)
+            }
+        }
+    }
+}
+
+/// every successful link made through this crate since the mappings were
+/// last cleared or a vfs was created, usvfs has no way to list them other
+/// than the dump
+static MAPPINGS: Mutex<Vec<MappingRecord>> = Mutex::new(Vec::new());
+
+/// counts changes to the mappings and the connection made through this
+/// crate, so anything derived from the mapping tree can tell it is stale
+static MAPPINGS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// note that the mapping tree may have changed, see MAPPINGS_GENERATION
+fn mappings_changed() {
+    MAPPINGS_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// the current value of MAPPINGS_GENERATION
+fn mappings_generation() -> u64 {
+    MAPPINGS_GENERATION.load(Ordering::Relaxed)
 }
 
 /// removes all virtual mappings
 pub fn clear_virtual_mappings() {
+    reset_virtual_mappings();
+}
+
+/// removes all virtual mappings like clear_virtual_mappings() and returns
+/// how many were removed. The connection stays open. The count is that of
+/// recorded_mappings(), usvfs itself doesn't say how many it dropped, so
+/// links made without this crate aren't counted
+pub fn reset_virtual_mappings() -> usize {
+    let mut mappings = lock_rules(&MAPPINGS);
+    let count = mappings.len();
+    mappings.clear();
     unsafe { usvfsClearVirtualMappings() };
+    mappings_changed();
+    count
+}
+
+/// every successful link made through this crate since the mappings were
+/// last cleared or a vfs was created
+pub fn recorded_mappings() -> Vec<MappingRecord> {
+    lock_rules(&MAPPINGS).clone()
+}
+
+/// disconnect from the VFS and create it again from params, then link
+/// everything that was linked through this crate before.
+///
+/// This is meant for recovering from a VFS that got into a bad state. The VFS
+/// is created rather than connected to, so it is reset and ends up with exactly
+/// the recorded mappings. Only links made with the functions of this crate in
+/// this process are known though, mappings made by other controllers are lost,
+/// and so are the blacklist, skip-lists and forced library loads, which have to
+/// be set up again. If a mapping fails to link again the first failure is
+/// returned, the mappings after it are still attempted
+pub fn reconnect(params: &ParametersHandle) -> Result<(), UsvfsError> {
+    let mappings = lock_rules(&MAPPINGS).clone();
+    disconnect_vfs();
+    create_vfs(params)?;
+
+    let mut first_error = None;
+    for mapping in &mappings {
+        if let Err(error) = mapping.apply() {
+            first_error.get_or_insert(error);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
 }
 
 /// link a file virtually
@@ -187,16 +890,45 @@ pub fn clear_virtual_mappings() {
 ///   - link directory (dynamic)
 ///   - delete file
 ///   - delete directory
+///
+/// The dynamic directory link and the delete operations are only planned
+/// upstream, usvfs does not export a function for them. The closest thing to
+/// a dynamic link is a static link with LinkFlags::MONITOR_CHANGES, which still
+/// enumerates every file up front but picks up later changes to the source.
 /// Maybe:
 ///   - rename/move (= copy + delete)
 ///   - copy-on-write semantics (changes to files are done in a separate copy of the file, the original is kept on disc but hidden)
-pub fn virtually_link_file(source: &str, destination: &str, flags: u32) -> Result<(), ()> {
-    unsafe {
-        match usvfsVirtualLinkFile(widen!(source), widen!(destination), flags) {
-            true => Ok(()),
-            false => Err(()),
-        }
-    }
+///
+/// A source longer than MAX_PATH is passed to usvfs with the `\\?\` prefix
+/// so usvfs can open it, and recorded with it, see widen_path()
+pub fn virtually_link_file(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    flags: LinkFlags,
+) -> Result<(), UsvfsError> {
+    link_wide(
+        MappingKind::File,
+        &widen_path(source.as_ref()),
+        &widen(destination.as_ref()),
+        flags,
+    )
+}
+
+/// like virtually_link_file(), but takes paths that are already UTF-16,
+/// e.g. from other Win32 calls, and passes them through as they are.
+/// A null terminator is appended if a path doesn't end in one, a null
+/// anywhere before the end is rejected with InteriorNul
+pub fn virtually_link_file_wide(
+    source: &[u16],
+    destination: &[u16],
+    flags: LinkFlags,
+) -> Result<(), UsvfsError> {
+    link_wide(
+        MappingKind::File,
+        &null_terminated(source)?,
+        &null_terminated(destination)?,
+        flags,
+    )
 }
 
 /// link a directory virtually. This static variant recursively links all files individually, change notifications
@@ -210,148 +942,897 @@ pub fn virtually_link_file(source: &str, destination: &str, flags: u32) -> Resul
 ///   - link directory (dynamic)
 ///   - delete file
 ///   - delete directory
+///
+/// The dynamic directory link and the delete operations are only planned
+/// upstream, usvfs does not export a function for them. The closest thing to
+/// a dynamic link is a static link with LinkFlags::MONITOR_CHANGES, which still
+/// enumerates every file up front but picks up later changes to the source.
 /// Maybe:
 ///   - rename/move (= copy + delete)
 ///   - copy-on-write semantics (changes to files are done in a separate copy of the file, the original is kept on disc but hidden)
+///
+/// Long sources get the `\\?\` prefix like for virtually_link_file()
 pub fn virtually_link_directory_static(
-    source: &str,
-    destination: &str,
-    flags: u32,
-) -> Result<(), ()> {
-    unsafe {
-        match usvfsVirtualLinkDirectoryStatic(widen!(source), widen!(destination), flags) {
-            true => Ok(()),
-            false => Err(()),
-        }
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    flags: LinkFlags,
+) -> Result<(), UsvfsError> {
+    link_wide(
+        MappingKind::DirectoryStatic,
+        &widen_path(source.as_ref()),
+        &widen(destination.as_ref()),
+        flags,
+    )
+}
+
+/// like virtually_link_directory_static(), but takes paths that are
+/// already UTF-16 and passes them through as they are. A null terminator
+/// is appended if a path doesn't end in one, a null anywhere before the
+/// end is rejected with InteriorNul
+pub fn virtually_link_directory_static_wide(
+    source: &[u16],
+    destination: &[u16],
+    flags: LinkFlags,
+) -> Result<(), UsvfsError> {
+    link_wide(
+        MappingKind::DirectoryStatic,
+        &null_terminated(source)?,
+        &null_terminated(destination)?,
+        flags,
+    )
+}
+
+/// link the files below source that predicate accepts, each to the same
+/// relative path below destination.
+///
+/// usvfs enumerates the files of a static directory link itself, so there is
+/// no way to filter them per file. Instead this walks source in Rust and
+/// links every accepted file on its own with virtually_link_file(), no
+/// directory link is made. That means:
+///   - predicate is called with the source path of every file, subdirectories
+///     are always walked
+///   - the skip lists only apply to usvfs's own directory links, check them
+///     in predicate if needed
+///   - like for any file link, the directories below destination have to
+///     exist, at least virtually
+///   - flags are validated as for a directory link, then the ones that
+///     only apply to directories are dropped from the file links
+///
+/// The whole tree is read before anything is linked. Files are linked in
+/// order of their names, directory by directory, and linking stops at the
+/// first failure, the files linked before it stay linked
+pub fn virtually_link_directory_static_filtered(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    flags: LinkFlags,
+    predicate: impl Fn(&Path) -> bool,
+) -> Result<(), UsvfsError> {
+    flags.validate_for(MappingKind::DirectoryStatic)?;
+    let mut files = Vec::new();
+    collect_files(source.as_ref(), destination.as_ref(), &mut files)?;
+    for (file, target) in files.iter().filter(|(file, _)| predicate(file)) {
+        virtually_link_file(file, target, flags & LinkFlags::FILE_FLAGS)?;
     }
+    Ok(())
 }
 
-/// gets the instance name of the current VFS and places it into buffer
-pub fn get_current_VFS_name(buffer: &mut [u8]) {
-    unsafe { usvfsGetCurrentVFSName(buffer.as_mut_ptr(), buffer.len()) }
+/// link every file below source like virtually_link_directory_static_filtered()
+/// with a predicate accepting everything, calling progress with the number
+/// of files linked so far and the total after each one, e.g. to drive a
+/// progress bar. usvfs's own static directory link can't report progress,
+/// so this always takes the slower route of linking file by file and the
+/// notes on virtually_link_directory_static_filtered() apply
+pub fn virtually_link_directory_static_progress(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    flags: LinkFlags,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<(), UsvfsError> {
+    flags.validate_for(MappingKind::DirectoryStatic)?;
+    let mut files = Vec::new();
+    collect_files(source.as_ref(), destination.as_ref(), &mut files)?;
+    for (done, (file, target)) in files.iter().enumerate() {
+        virtually_link_file(file, target, flags & LinkFlags::FILE_FLAGS)?;
+        progress(done + 1, files.len());
+    }
+    Ok(())
 }
 
-/// spawn a new process that can see the virtual file system. The signature is identical to CreateProcess
-/// but a bit more rusty. Still requires windows stuff.
-/// I will impliment some way to pass these to C as null, since in many cases the user does not
-/// care to have these back.
-pub fn create_process_hooked(
-    application_name: &str,
-    command_line: &str,
-    process_attributes: &mut SECURITY_ATTRIBUTES,
-    thread_attributes: &mut SECURITY_ATTRIBUTES,
-    inherit_handles: bool,
-    current_dir: &str,
-    startup_information: &mut STARTUPINFOW,
-    process_information: &mut PROCESS_INFORMATION,
-) -> Result<(), ()> {
-    unsafe {
-        match usvfsCreateProcessHooked(
-            widen!(application_name),
-            widen!(command_line).cast_mut(),
-            process_attributes,
-            thread_attributes,
-            inherit_handles,
-            0,
-            ptr::null_mut(),
-            widen!(current_dir),
-            startup_information,
-            process_information,
-        ) {
-            true => Ok(()),
-            false => Err(()),
+/// link every file below source one by one like
+/// virtually_link_directory_static_filtered(), leaving out the files the
+/// skip lists of this crate cover, and return the virtual destinations of
+/// the linked files, e.g. to log them or to know what to tear down later
+///
+/// The skip lists are applied as usvfs applies them to its own directory
+/// links, ignoring case: a file is left out if its name ends with one of
+/// skip_file_suffixes(), or if a directory between source and the file is
+/// named like one of skipped_directories(). Only the entries added through
+/// this crate are known, see skipped_directories(). Skipped files are left
+/// out whatever the flags, FAIL_IF_SKIPPED doesn't turn them into an error
+///
+/// Linking stops at the first failure. The files linked before it stay
+/// linked and are in recorded_mappings()
+pub fn virtually_link_directory_static_listed(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    flags: LinkFlags,
+) -> Result<Vec<PathBuf>, UsvfsError> {
+    flags.validate_for(MappingKind::DirectoryStatic)?;
+    let source = source.as_ref();
+    let mut files = Vec::new();
+    collect_files(source, destination.as_ref(), &mut files)?;
+
+    let suffixes = lowercase(&skip_file_suffixes());
+    let directories = lowercase(&skipped_directories());
+    let mut linked = Vec::new();
+    for (file, target) in files {
+        let relative = file.strip_prefix(source).unwrap_or(&file);
+        if is_skipped(relative, &suffixes, &directories) {
+            continue;
         }
+        virtually_link_file(&file, &target, flags & LinkFlags::FILE_FLAGS)?;
+        linked.push(target);
     }
+    Ok(linked)
 }
 
-/// begin logging on the VFS
-pub fn init_logging(toLocal: bool) {
-    unsafe { usvfsInitLogging(toLocal) }
+/// every string of strings in lowercase
+fn lowercase(strings: &[String]) -> Vec<String> {
+    strings.iter().map(|string| string.to_lowercase()).collect()
 }
 
-/// get a single log message
-/// not sure if this currently works upstream
-/// should take a destination buffer for the log message
-/// set blocking to false since true isn't implimented upstream
-pub fn get_log_message(dst: &mut [u8], blocking: bool) {
-    unsafe {
-        // TODO this bool should cause error handeling and return some kind of result
-        _ = usvfsGetLogMessage(dst.as_mut_ptr(), &mut dst.len(), blocking)
-    }
+/// whether the file at relative, below the linked directory, is covered by
+/// the lowercase suffixes or directory names
+fn is_skipped(relative: &Path, suffixes: &[String], directories: &[String]) -> bool {
+    let name = relative
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let in_skipped_directory = relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| {
+            let component = component.as_os_str().to_string_lossy().to_lowercase();
+            directories.contains(&component)
+        });
+    in_skipped_directory
+        || suffixes
+            .iter()
+            .any(|suffix| name.ends_with(suffix.as_str()))
 }
 
-/// retrieves a readable representation of the vfs tree
-/// the buffer to write to can be null if you only want to determine the required buffer size
-/// size is a pointer to the variable that contains the buffer and is updated to the size on return
-/// I'm not sure how exactly this will work from Rust, currently unstable and not tested
-pub fn create_vfs_dump(buffer: &mut [u8], size: *mut usize) -> Result<(), ()> {
-    unsafe {
-        match usvfsCreateVFSDump(buffer.as_mut_ptr(), size) {
-            true => Ok(()),
-            false => Err(()),
+/// every file below source, paired with the same relative path below
+/// destination, in the order virtually_link_directory_static_filtered()
+/// links them
+fn collect_files(
+    source: &Path,
+    destination: &Path,
+    files: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), UsvfsError> {
+    let io_error = |error: std::io::Error| UsvfsError::Io {
+        kind: error.kind(),
+        message: format!("{}: {error}", source.display()),
+    };
+    let mut entries = std::fs::read_dir(source)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(io_error)?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let target = destination.join(entry.file_name());
+        match path.is_dir() {
+            true => collect_files(&path, &target, files)?,
+            false => files.push((path, target)),
         }
     }
+    Ok(())
 }
 
-/// add an executable to the blacklist so it doesn't get exposed
-/// to the virtual file system
-pub fn blacklist_executable(executableName: &str) {
-    unsafe { usvfsBlacklistExecutable(widen_mut!(executableName)) }
+/// makes sure wide ends in its only null, appending one if it has none
+fn null_terminated(wide: &[u16]) -> Result<Cow<'_, [u16]>, UsvfsError> {
+    match wide.iter().position(|&unit| unit == 0x00) {
+        Some(end) if end + 1 == wide.len() => Ok(Cow::Borrowed(wide)),
+        Some(_) => Err(UsvfsError::InteriorNul),
+        None => Ok(Cow::Owned(wide.iter().copied().chain([0x00]).collect())),
+    }
 }
 
-/// clears the executable blacklist
-pub fn clear_executable_blacklist() {
-    unsafe { usvfsClearExecutableBlacklist() }
+/// link the null terminated source and destination with the function
+/// for kind and record the mapping if it succeeded. Flags kind doesn't
+/// accept are rejected without calling usvfs
+fn link_wide(
+    kind: MappingKind,
+    source: &[u16],
+    destination: &[u16],
+    flags: LinkFlags,
+) -> Result<(), UsvfsError> {
+    flags.validate_for(kind)?;
+    let linked = unsafe {
+        match kind {
+            MappingKind::File => {
+                usvfsVirtualLinkFile(source.as_ptr(), destination.as_ptr(), flags.bits())
+            }
+            MappingKind::DirectoryStatic => {
+                usvfsVirtualLinkDirectoryStatic(source.as_ptr(), destination.as_ptr(), flags.bits())
+            }
+        }
+    };
+    let code = last_error();
+
+    // drop the terminator to get the paths back
+    let source = PathBuf::from(OsString::from_wide(&source[..source.len() - 1]));
+    let destination = PathBuf::from(OsString::from_wide(&destination[..destination.len() - 1]));
+    match linked {
+        true => {
+            lock_rules(&MAPPINGS).push(MappingRecord {
+                source,
+                destination,
+                flags,
+                kind,
+            });
+            mappings_changed();
+            Ok(())
+        }
+        false => Err(UsvfsError::LinkFailed {
+            source: source.display().to_string(),
+            destination: destination.display().to_string(),
+            code,
+        }),
+    }
 }
 
-/// adds a file suffix to a list to skip during file linking
-/// .txt and some_file.txt are both valid file suffixes,
-/// not to be confused with file extensions
-pub fn add_skip_file_suffix(fileSuffix: &str) {
-    unsafe { usvfsAddSkipFileSuffix(widen_mut!(fileSuffix)) }
+/// link every (source, destination, flags) entry with virtually_link_file(),
+/// carrying on past failures. All failures are returned together with the
+/// index of the entry that caused them
+pub fn link_files(
+    entries: &[(PathBuf, PathBuf, LinkFlags)],
+) -> Result<(), Vec<(usize, UsvfsError)>> {
+    let failures: Vec<(usize, UsvfsError)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (source, destination, flags))| {
+            virtually_link_file(source, destination, *flags)
+                .err()
+                .map(|error| (index, error))
+        })
+        .collect();
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(failures),
+    }
 }
 
-/// clears the file suffix skip-list
-pub fn clear_skip_file_suffixes() {
-    unsafe { usvfsClearSkipFileSuffixes() }
+/// link every (source, destination, flags) entry with virtually_link_file(),
+/// stopping at the first failure. Entries before it stay linked
+pub fn link_files_stop_on_error(
+    entries: &[(PathBuf, PathBuf, LinkFlags)],
+) -> Result<(), (usize, UsvfsError)> {
+    for (index, (source, destination, flags)) in entries.iter().enumerate() {
+        virtually_link_file(source, destination, *flags).map_err(|error| (index, error))?;
+    }
+    Ok(())
 }
 
-/// Adds a directory name that will be skipped during directory
-/// linking. Not a path.
-///
-/// Any directory matching the name will be
-/// skipped, regardless of it's path.
+/// size of the buffer current_vfs_name() hands to usvfs, large enough
+/// for any instance name usvfs can create shared memory for
+const VFS_NAME_BUFFER_SIZE: usize = 260;
+
+/// gets the instance name of the current VFS and places it into buffer,
+/// cut to fit and null terminated. Returns the size in bytes, including
+/// the terminator, a buffer needs to hold the whole name, so a result
+/// larger than buffer means the name was cut and can be read again with
+/// a buffer of that size. An empty buffer is left alone
 ///
-/// For example if .git is added, any sub-path or root-path
-/// containing a .git directory will have the .git directly
-/// skipped during directory linking.
-pub fn add_skip_directory(directory: &str) {
-    unsafe { usvfsAddSkipDirectory(widen_mut!(directory)) }
+/// usvfsGetCurrentVFSName cuts the name silently and doesn't report its
+/// length, so the name is read into a buffer large enough for any name
+/// first and copied over from there. Prefer current_vfs_name() unless you
+/// need to reuse a buffer
+pub fn get_current_VFS_name(buffer: &mut [u8]) -> usize {
+    let mut name = vec![0u8; VFS_NAME_BUFFER_SIZE];
+    unsafe { usvfsGetCurrentVFSName(name.as_mut_ptr(), name.len()) };
+    let length = name
+        .iter()
+        .position(|&byte| byte == 0x00)
+        .unwrap_or(name.len());
+
+    if let Some(capacity) = buffer.len().checked_sub(1) {
+        let copied = length.min(capacity);
+        buffer[..copied].copy_from_slice(&name[..copied]);
+        buffer[copied] = 0x00;
+    }
+    length + 1
 }
 
-/// clears the directory skip-list
-pub fn clear_skip_directories() {
-    unsafe { usvfsClearSkipDirectories() }
+/// gets the instance name of the current VFS, or None if
+/// there is no name, eg when not connected to a VFS
+pub fn current_vfs_name() -> Option<String> {
+    let mut buffer = vec![0u8; VFS_NAME_BUFFER_SIZE];
+    get_current_VFS_name(&mut buffer);
+    let name = narrow_to_string(&buffer);
+
+    match name.is_empty() {
+        true => None,
+        false => Some(name),
+    }
+}
+
+/// how many times vfs_process_list() retries when processes get
+/// hooked in between learning the count and filling the buffer
+const PROCESS_LIST_RETRIES: usize = 5;
+
+/// gets the ids of all processes currently hooked into the VFS
+///
+/// The list is fetched by asking usvfs for the count first and then
+/// filling a buffer of that size. If more processes got hooked in the
+/// meantime this retries a few times before giving up with ERROR_MORE_DATA
+pub fn vfs_process_list() -> Result<Vec<u32>, UsvfsError> {
+    let mut count: size_t = 0;
+    unsafe {
+        if !usvfsGetVFSProcessList(&mut count, ptr::null_mut()) {
+            return Err(UsvfsError::ProcessListFailed { code: last_error() });
+        }
+    }
+
+    for _ in 0..PROCESS_LIST_RETRIES {
+        let mut processIDs = vec![0u32; count];
+        let capacity = count;
+        unsafe {
+            if !usvfsGetVFSProcessList(&mut count, processIDs.as_mut_ptr()) {
+                return Err(UsvfsError::ProcessListFailed { code: last_error() });
+            }
+        }
+        // count is updated to the real number of processes, if it
+        // grew past the buffer the list was truncated and has to be fetched again
+        if count <= capacity {
+            processIDs.truncate(count);
+            return Ok(processIDs);
+        }
+    }
+
+    Err(UsvfsError::ProcessListFailed {
+        code: ERROR_MORE_DATA.0,
+    })
+}
+
+/// owns a buffer allocated by usvfs with calloc() and frees it
+/// on drop, so early returns can't leak it
+struct CBuffer<T>(*mut T);
+
+impl<T> Drop for CBuffer<T> {
+    fn drop(&mut self) {
+        unsafe { libc::free(self.0.cast()) }
+    }
+}
+
+/// gets the ids of all processes currently hooked into the VFS
+///
+/// Unlike vfs_process_list() usvfs allocates the list itself in a single
+/// call, so there is no window for the count to change between calls.
+/// The buffer is allocated with calloc() in usvfs and has to be released
+/// with free(), both usvfs and Rust use the universal CRT so the libc
+/// free() here releases it from the same heap
+pub fn vfs_process_list_alloc() -> Result<Vec<u32>, UsvfsError> {
+    let mut count: size_t = 0;
+    let mut buffer: *mut u32 = ptr::null_mut();
+    unsafe {
+        let success = usvfsGetVFSProcessList2(&mut count, &mut buffer);
+        let buffer = CBuffer(buffer);
+        if !success {
+            return Err(UsvfsError::ProcessListFailed { code: last_error() });
+        }
+        // usvfs leaves the buffer null when there are no processes
+        if buffer.0.is_null() || count == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(std::slice::from_raw_parts(buffer.0, count).to_vec())
+    }
+}
+
+/// The optional inputs of CreateProcess for create_process_hooked(). The
+/// default leaves every one of them out: the security attributes are passed
+/// to C as null, no handles are inherited, no creation flags are set, the
+/// current directory is inherited from the calling process and a default
+/// startup information is used
+#[derive(Default)]
+pub struct CreateProcessOptions<'a> {
+    pub process_attributes: Option<&'a mut SECURITY_ATTRIBUTES>,
+    pub thread_attributes: Option<&'a mut SECURITY_ATTRIBUTES>,
+    pub inherit_handles: bool,
+    /// passed on to CreateProcess, see create_process_hooked()
+    pub creation_flags: PROCESS_CREATION_FLAGS,
+    pub current_dir: Option<&'a Path>,
+    pub startup_information: Option<&'a mut STARTUPINFOW>,
+    /// filled in with the handles and ids of the new process. If None, the
+    /// handles CreateProcess returns are closed again since nobody could
+    /// close them otherwise
+    pub process_information: Option<&'a mut PROCESS_INFORMATION>,
+}
+
+/// spawn a new process that can see the virtual file system. Takes the same
+/// inputs as CreateProcess, with the optional ones in options, but a bit more
+/// rusty. Still requires windows stuff.
+///
+/// The creation flags are passed on to CreateProcess. usvfs always creates the process
+/// suspended to queue the hook injection and resumes it afterwards, unless CREATE_SUSPENDED is
+/// given here, in which case the main thread stays suspended until the caller resumes
+/// it. The process delay from the parameters is spent inside the child once its main
+/// thread runs, so with CREATE_SUSPENDED it only starts counting after resuming
+pub fn create_process_hooked(
+    application_name: impl AsRef<Path>,
+    command_line: &str,
+    options: CreateProcessOptions<'_>,
+) -> Result<(), UsvfsError> {
+    create_process_hooked_wide(
+        application_name.as_ref(),
+        // CreateProcessW may modify the command line in place, so it needs its own mutable buffer
+        &mut widen(command_line),
+        None,
+        options,
+    )
+}
+
+/// create_process_hooked() with the command line already widened and an
+/// optional UTF-16 environment block, which needs CREATE_UNICODE_ENVIRONMENT
+/// in the creation flags
+fn create_process_hooked_wide(
+    application_name: &Path,
+    command_line: &mut [u16],
+    environment: Option<&[u16]>,
+    options: CreateProcessOptions<'_>,
+) -> Result<(), UsvfsError> {
+    let CreateProcessOptions {
+        process_attributes,
+        thread_attributes,
+        inherit_handles,
+        creation_flags,
+        current_dir,
+        startup_information,
+        process_information,
+    } = options;
+    let wide_application_name = widen(application_name);
+    let current_dir = current_dir.map(widen);
+
+    let mut default_startup_information = STARTUPINFOW {
+        cb: mem::size_of::<STARTUPINFOW>() as u32,
+        ..Default::default()
+    };
+    let mut default_process_information = PROCESS_INFORMATION::default();
+    let close_handles = process_information.is_none();
+    let startup_information = startup_information.unwrap_or(&mut default_startup_information);
+    let process_information = process_information.unwrap_or(&mut default_process_information);
+
+    unsafe {
+        match usvfsCreateProcessHooked(
+            wide_application_name.as_ptr(),
+            command_line.as_mut_ptr(),
+            process_attributes.map_or(ptr::null_mut(), |attributes| attributes),
+            thread_attributes.map_or(ptr::null_mut(), |attributes| attributes),
+            inherit_handles,
+            creation_flags.0,
+            // CreateProcessW only reads the environment, the pointer is mut for historical reasons
+            environment.map_or(ptr::null_mut(), |environment| {
+                environment.as_ptr().cast_mut().cast()
+            }),
+            current_dir
+                .as_ref()
+                .map_or(ptr::null(), |current_dir| current_dir.as_ptr()),
+            startup_information,
+            process_information,
+        ) {
+            true => {
+                if close_handles {
+                    _ = CloseHandle(process_information.hThread);
+                    _ = CloseHandle(process_information.hProcess);
+                }
+                Ok(())
+            }
+            false => {
+                // before anything else can overwrite the last error
+                let win32_code = last_error();
+                Err(UsvfsError::ProcessSpawnFailed {
+                    application_name: application_name.display().to_string(),
+                    win32_code,
+                })
+            }
+        }
+    }
+}
+
+/// spawn an executable that can see the virtual file system with everything
+/// but the command line left at its default. The command line is passed on as
+/// is, so like with CreateProcess it should start with the quoted application.
+pub fn spawn_hooked(application: &Path, command_line: &str) -> Result<HookedProcess, UsvfsError> {
+    let mut process_information = PROCESS_INFORMATION::default();
+    create_process_hooked(
+        application,
+        command_line,
+        CreateProcessOptions {
+            process_information: Some(&mut process_information),
+            ..Default::default()
+        },
+    )?;
+    unsafe { Ok(HookedProcess::from_raw(process_information)) }
+}
+
+/// like spawn_hooked(), but the main thread of the process stays suspended until
+/// HookedProcess::resume_main_thread() is called, so it can be set up before it
+/// runs. usvfs has only queued the injection on the suspended thread at that
+/// point, the hook dll loads once it is resumed, so nothing done before
+/// resume_main_thread() is hooked yet. The process delay from the parameters
+/// only starts once the thread is resumed, see create_process_hooked()
+pub fn spawn_suspended(
+    application: &Path,
+    command_line: &str,
+) -> Result<HookedProcess, UsvfsError> {
+    let mut process_information = PROCESS_INFORMATION::default();
+    create_process_hooked(
+        application,
+        command_line,
+        CreateProcessOptions {
+            creation_flags: CREATE_SUSPENDED,
+            process_information: Some(&mut process_information),
+            ..Default::default()
+        },
+    )?;
+    unsafe { Ok(HookedProcess::from_raw(process_information)) }
+}
+
+/// set once usvfsInitLogging has been called, calling it again
+/// makes usvfs register its log handlers a second time
+static LOGGING_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// begin logging on the VFS
+#[deprecated(note = "use try_init_logging(), which reports repeated initialization")]
+pub fn init_logging(toLocal: bool) {
+    _ = try_init_logging(toLocal);
+}
+
+/// begin logging on the VFS. Logging can only be initialized once per
+/// process, later calls fail with LoggingAlreadyInitialized and leave
+/// the existing logger as is
+pub fn try_init_logging(to_local: bool) -> Result<(), UsvfsError> {
+    if LOGGING_INITIALIZED.swap(true, Ordering::SeqCst) {
+        return Err(UsvfsError::LoggingAlreadyInitialized);
+    }
+    unsafe { usvfsInitLogging(to_local) };
+    Ok(())
+}
+
+/// get a single log message
+/// not sure if this currently works upstream
+/// should take a destination buffer for the log message
+/// set blocking to false since true isn't implimented upstream
+pub fn get_log_message(dst: &mut [u8], blocking: bool) {
+    unsafe {
+        // TODO this bool should cause error handeling and return some kind of result
+        _ = usvfsGetLogMessages(dst.as_mut_ptr(), dst.len(), blocking)
+    }
+}
+
+/// size of the buffer each log message is read into. usvfsGetLogMessages takes
+/// the size by value and can't report the real length, and a message is gone
+/// from the queue once read, so a short buffer can't be retried with a larger
+/// one. Instead this matches the slots of usvfs's shared log queue, which hold
+/// at most this many bytes including the terminator, so every queued message
+/// fits. Longer messages are already cut to that size when they are logged,
+/// see LogMessage::truncated
+const LOG_MESSAGE_BUFFER_SIZE: usize = 1024;
+
+/// drains all log messages currently queued by the VFS without blocking.
+/// The iterator ends as soon as usvfs reports no more messages, so it
+/// can be polled in a loop to pump the log somewhere else
+pub fn drain_log_messages() -> impl Iterator<Item = String> {
+    let mut buffer = vec![0u8; LOG_MESSAGE_BUFFER_SIZE];
+    std::iter::from_fn(move || {
+        buffer.fill(0x00);
+        unsafe {
+            match usvfsGetLogMessages(buffer.as_mut_ptr(), buffer.len(), false) {
+                true => Some(narrow_to_string(&buffer)),
+                false => None,
+            }
+        }
+    })
+}
+
+/// writes a readable representation of the vfs tree to buffer, for callers
+/// that manage the buffer themselves, otherwise see vfs_dump()
+///
+/// On input size is how many bytes of buffer usvfs may write, capped at the
+/// length of buffer and ignored for None. On return it holds the length of
+/// the whole dump in bytes, without the null terminator, also when the call
+/// fails. A dump that doesn't fit together with its null terminator is
+/// truncated and fails with DumpFailed and ERROR_MORE_DATA, so passing None
+/// and then a buffer of size + 1 bytes allocates exactly once, unless links
+/// change in between
+pub fn create_vfs_dump(buffer: Option<&mut [u8]>, size: &mut usize) -> Result<(), UsvfsError> {
+    let (pointer, capacity) = match buffer {
+        Some(buffer) => (buffer.as_mut_ptr(), (*size).min(buffer.len())),
+        None => (ptr::null_mut(), 0),
+    };
+    *size = capacity;
+    let written = unsafe { usvfsCreateVFSDump(pointer, size) };
+    match (written, *size < capacity) {
+        (true, true) => Ok(()),
+        // usvfs reports success when only the null terminator is missing
+        (_, false) => Err(UsvfsError::DumpFailed {
+            code: ERROR_MORE_DATA.0,
+        }),
+        (false, true) => Err(UsvfsError::DumpFailed { code: last_error() }),
+    }
+}
+
+/// retrieves a readable representation of the vfs tree as a String
+///
+/// The buffer is sized by first asking usvfs for the length of the dump.
+/// The reported size excludes the null terminator and usvfs truncates to
+/// fit it in, so one extra byte is allocated and the result is cut at
+/// the first null. If links were added in the meantime this retries a few
+/// times before giving up with ERROR_MORE_DATA, like vfs_process_list()
+pub fn vfs_dump() -> Result<String, UsvfsError> {
+    Ok(narrow_to_string(&dump_buffer()?))
+}
+
+/// counts the files and directories in the vfs tree, e.g. for a summary
+/// in diagnostics, see count_dump_nodes()
+pub fn dump_stats() -> Result<DumpStats, UsvfsError> {
+    vfs_dump().map(|dump| count_dump_nodes(&dump))
+}
+
+/// writes the readable representation of the vfs tree to writer, eg a log
+/// file, without decoding it into a String first. The bytes are written as
+/// usvfs produced them, without the null terminator
+pub fn dump_to_writer<W: Write>(writer: &mut W) -> Result<(), UsvfsError> {
+    let buffer = dump_buffer()?;
+    let length = buffer
+        .iter()
+        .position(|&byte| byte == 0x00)
+        .unwrap_or(buffer.len());
+    writer
+        .write_all(&buffer[..length])
+        .map_err(|error| UsvfsError::Io {
+            kind: error.kind(),
+            message: format!("failed to write VFS dump: {error}"),
+        })
+}
+
+/// writes the dump like dump_to_writer() to a file handle that is already
+/// open, e.g. a temp file created with specific flags. The handle is only
+/// borrowed and stays open. Partial writes are continued until everything
+/// is written, and a failed write or an invalid handle is returned as Io.
+/// To write to a std::fs::File pass `&mut &file` to dump_to_writer()
+pub fn dump_to_handle(handle: HANDLE) -> Result<(), UsvfsError> {
+    if handle.is_invalid() {
+        return Err(UsvfsError::Io {
+            kind: std::io::ErrorKind::InvalidInput,
+            message: "failed to write VFS dump: invalid handle".to_owned(),
+        });
+    }
+    // File closes its handle on drop, which would close the caller's
+    let mut file = mem::ManuallyDrop::new(unsafe { File::from_raw_handle(handle.0) });
+    dump_to_writer(&mut *file)
+}
+
+/// the length of the dump create_vfs_dump() would write, in bytes and
+/// without the null terminator, so a buffer for it needs one more byte.
+/// Fails with DumpFailed if usvfs reports nothing, even an empty tree
+/// has a root line
+pub fn vfs_dump_size() -> Result<usize, UsvfsError> {
+    let mut size: size_t = 0;
+    // probing with an empty buffer reports failure since the dump
+    // doesn't fit, only the size is of interest here
+    unsafe { _ = usvfsCreateVFSDump(ptr::null_mut(), &mut size) };
+    match size {
+        0 => Err(UsvfsError::DumpFailed { code: last_error() }),
+        _ => Ok(size),
+    }
+}
+
+/// how many times dump_buffer() retries when links get added in between
+/// sizing the dump and filling the buffer
+const DUMP_RETRIES: usize = 5;
+
+/// the null terminated dump usvfs writes, see vfs_dump()
+fn dump_buffer() -> Result<Vec<u8>, UsvfsError> {
+    let mut length = vfs_dump_size()?;
+    for _ in 0..DUMP_RETRIES {
+        let mut buffer = vec![0u8; length + 1];
+        let mut size = buffer.len();
+        match create_vfs_dump(Some(&mut buffer), &mut size) {
+            Ok(()) => return Ok(buffer),
+            // the tree grew since it was sized and the dump got truncated,
+            // size is the new length so fetch it again with a larger buffer
+            Err(UsvfsError::DumpFailed { code }) if code == ERROR_MORE_DATA.0 => length = size,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Err(UsvfsError::DumpFailed {
+        code: ERROR_MORE_DATA.0,
+    })
+}
+
+/// whether virtual_path is redirected somewhere by the VFS. This looks the
+/// path up in the dump, so a dump that can't be made or parsed counts as
+/// nothing being mapped
+pub fn is_mapped(virtual_path: impl AsRef<Path>) -> bool {
+    resolve(virtual_path).is_some()
+}
+
+/// the real path virtual_path is redirected to, None if it isn't mapped
+/// or the dump can't be made or parsed, see is_mapped()
+pub fn resolve(virtual_path: impl AsRef<Path>) -> Option<PathBuf> {
+    let dump = vfs_dump().ok()?;
+    let tree = parse_vfs_dump(&dump).ok()?;
+    tree.find(virtual_path)?.source.clone()
+}
+
+/// executables blacklisted through this crate. usvfs has no way to read
+/// the blacklist back, so this mirror is what guards restore from
+static BLACKLIST: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// file suffixes skipped through this crate, see BLACKLIST
+static SKIP_FILE_SUFFIXES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// directory names skipped through this crate, see BLACKLIST
+static SKIP_DIRECTORIES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// (process name, library path) pairs force loaded through this crate, see BLACKLIST
+static FORCE_LOADS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// fails with the index of the first entry that contains a null byte,
+/// which would cut it short on its way to usvfs
+fn check_entries(entries: &[&str]) -> Result<(), UsvfsError> {
+    match entries.iter().position(|entry| entry.contains('\0')) {
+        Some(index) => Err(UsvfsError::InteriorNulAt { index }),
+        None => Ok(()),
+    }
+}
+
+/// lock one of the rule mirrors. They only hold plain lists, so a
+/// panic while one was locked can't leave it in a broken state
+fn lock_rules<T>(rules: &Mutex<T>) -> MutexGuard<'_, T> {
+    rules.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// add an executable to the blacklist so it doesn't get exposed
+/// to the virtual file system
+pub fn blacklist_executable(executableName: &str) {
+    lock_rules(&BLACKLIST).push(executableName.to_owned());
+    let mut executableName = widen(executableName);
+    unsafe { usvfsBlacklistExecutable(executableName.as_mut_ptr()) }
+}
+
+/// blacklist every executable in names like blacklist_executable(). The
+/// names are checked first, if one contains a null byte none is added and
+/// InteriorNulAt reports its index
+pub fn blacklist_executables(names: &[&str]) -> Result<(), UsvfsError> {
+    check_entries(names)?;
+    names.iter().for_each(|name| blacklist_executable(name));
+    Ok(())
+}
+
+/// clears the executable blacklist
+pub fn clear_executable_blacklist() {
+    lock_rules(&BLACKLIST).clear();
+    unsafe { usvfsClearExecutableBlacklist() }
+}
+
+/// the executables blacklisted through this crate since the blacklist was
+/// last cleared. Entries added by other controllers of the same VFS are not
+/// included since usvfs can't report them
+pub fn blacklisted_executables() -> Vec<String> {
+    lock_rules(&BLACKLIST).clone()
+}
+
+/// adds a file suffix to a list to skip during file linking
+/// .txt and some_file.txt are both valid file suffixes,
+/// not to be confused with file extensions
+pub fn add_skip_file_suffix(fileSuffix: &str) {
+    lock_rules(&SKIP_FILE_SUFFIXES).push(fileSuffix.to_owned());
+    let mut fileSuffix = widen(fileSuffix);
+    unsafe { usvfsAddSkipFileSuffix(fileSuffix.as_mut_ptr()) }
+}
+
+/// add every suffix in suffixes like add_skip_file_suffix(), checking them
+/// first like blacklist_executables()
+pub fn add_skip_file_suffixes(suffixes: &[&str]) -> Result<(), UsvfsError> {
+    check_entries(suffixes)?;
+    suffixes
+        .iter()
+        .for_each(|suffix| add_skip_file_suffix(suffix));
+    Ok(())
+}
+
+/// clears the file suffix skip-list
+pub fn clear_skip_file_suffixes() {
+    lock_rules(&SKIP_FILE_SUFFIXES).clear();
+    unsafe { usvfsClearSkipFileSuffixes() }
+}
+
+/// the file suffixes added to the skip-list through this crate since it
+/// was last cleared, see skipped_directories()
+pub fn skip_file_suffixes() -> Vec<String> {
+    lock_rules(&SKIP_FILE_SUFFIXES).clone()
+}
+
+/// Adds a directory name that will be skipped during directory
+/// linking. Not a path.
+///
+/// Any directory matching the name will be
+/// skipped, regardless of it's path.
+///
+/// For example if .git is added, any sub-path or root-path
+/// containing a .git directory will have the .git directly
+/// skipped during directory linking.
+pub fn add_skip_directory(directory: &str) {
+    lock_rules(&SKIP_DIRECTORIES).push(directory.to_owned());
+    let mut directory = widen(directory);
+    unsafe { usvfsAddSkipDirectory(directory.as_mut_ptr()) }
+}
+
+/// add every name in directories like add_skip_directory(), checking them
+/// first like blacklist_executables()
+pub fn add_skip_directories(directories: &[&str]) -> Result<(), UsvfsError> {
+    check_entries(directories)?;
+    directories
+        .iter()
+        .for_each(|directory| add_skip_directory(directory));
+    Ok(())
+}
+
+/// clears the directory skip-list
+pub fn clear_skip_directories() {
+    lock_rules(&SKIP_DIRECTORIES).clear();
+    unsafe { usvfsClearSkipDirectories() }
+}
+
+/// the directory names added to the skip-list through this crate since it
+/// was last cleared. Entries added by other controllers of the same VFS are
+/// not included since usvfs can't report them
+pub fn skipped_directories() -> Vec<String> {
+    lock_rules(&SKIP_DIRECTORIES).clone()
 }
 
 /// adds a library to be force loaded when the given process is injected
 pub fn force_load_library(processName: &str, libraryPath: &str) {
-    unsafe { usvfsForceLoadLibrary(widen_mut!(processName), widen_mut!(libraryPath)) }
+    lock_rules(&FORCE_LOADS).push((processName.to_owned(), libraryPath.to_owned()));
+    let mut processName = widen(processName);
+    let mut libraryPath = widen(libraryPath);
+    unsafe { usvfsForceLoadLibrary(processName.as_mut_ptr(), libraryPath.as_mut_ptr()) }
 }
 
 /// clears all previous calls to force_load_library()
 pub fn clear_library_force_loads() {
+    lock_rules(&FORCE_LOADS).clear();
     unsafe { usvfsClearLibraryForceLoads() }
 }
 
-/// print debugging info about the vfs. The format is currently not
+/// the (process name, library path) pairs registered through this crate since
+/// the force loads were last cleared. Entries added by other controllers of
+/// the same VFS are not included since usvfs can't report them
+pub fn forced_libraries() -> Vec<(String, String)> {
+    lock_rules(&FORCE_LOADS).clone()
+}
+
+/// print debugging info about the vfs to the VFS log. The format is currently not
 /// fixed and may change between usvfs versions
-fn print_debug_info() {
+///
+/// usvfs only writes this to its log, there is no variant filling a buffer, so
+/// to capture it initialize logging and read it back with drain_log_messages()
+pub fn print_debug_info() {
     unsafe { usvfsPrintDebugInfo() }
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum LogLevel {
     Debug,
     Info,
@@ -359,38 +1840,128 @@ pub enum LogLevel {
     Error,
 }
 
+impl LogLevel {
+    /// the name usvfsLogLevelToString() returns for this level, without
+    /// calling into usvfs
+    pub fn as_static_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
 impl Display for LogLevel {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        unsafe {
-            let strLogLevel = CStr::from_ptr(usvfsLogLevelToString(*self))
-                .to_str()
-                .expect("Invalid Utf8");
-            write!(f, "{}", strLogLevel)
+        f.write_str(self.as_static_str())
+    }
+}
+
+/// parses the same lowercase names Display produces, ignoring case
+impl FromStr for LogLevel {
+    type Err = UsvfsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(UsvfsError::ParseFailed {
+                kind: "log level",
+                input: s.to_owned(),
+            }),
         }
     }
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum CrashDumpsType {
+    #[cfg_attr(feature = "serde", serde(rename = "none"))]
     Nil,
     Mini,
     Data,
     Full,
 }
 
+impl CrashDumpsType {
+    /// the name usvfsCrashDumpTypeToString() returns for this type, without
+    /// calling into usvfs
+    pub fn as_static_str(&self) -> &'static str {
+        match self {
+            Self::Nil => "none",
+            Self::Mini => "mini",
+            Self::Data => "data",
+            Self::Full => "full",
+        }
+    }
+}
+
 impl Display for CrashDumpsType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        unsafe {
-            let strLogLevel = CStr::from_ptr(usvfsCrashDumpTypeToString(*self))
-                .to_str()
-                .expect("Invalid Utf8");
-            write!(f, "{}", strLogLevel)
+        f.write_str(self.as_static_str())
+    }
+}
+
+/// parses the same lowercase names Display produces, ignoring case
+impl FromStr for CrashDumpsType {
+    type Err = UsvfsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::Nil),
+            "mini" => Ok(Self::Mini),
+            "data" => Ok(Self::Data),
+            "full" => Ok(Self::Full),
+            _ => Err(UsvfsError::ParseFailed {
+                kind: "crash dumps type",
+                input: s.to_owned(),
+            }),
         }
     }
 }
 
-#[link(name = "usvfs_x64")]
+/// the newest `.dmp` file in the crash dumps directory path, e.g. to attach
+/// it to a crash report after a hooked process crashed. An empty path means
+/// the current working directory, like for set_crash_dumps_path(). None if
+/// the directory holds no dumps or can't be read. Files are compared by
+/// their modification time, the name usvfs gives them isn't relied on
+pub fn latest_crash_dump(path: &Path) -> Option<PathBuf> {
+    let directory = match path.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => path,
+    };
+    std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("dmp"))
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok()?;
+            metadata.is_file().then(|| (modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+// usvfs ships a separate library per bitness, build.rs picks the matching one.
+// The parameter functions use the C calling convention while everything else is
+// declared WINAPI upstream, which only makes a difference on 32 bit
+#[cfg_attr(target_arch = "x86_64", link(name = "usvfs_x64"))]
+#[cfg_attr(target_arch = "x86", link(name = "usvfs_x86"))]
 extern "C" {
     fn usvfsCreateParameters() -> *mut Parameters;
     fn usvfsDupeParameters(p: *const Parameters) -> *mut Parameters;
@@ -405,7 +1976,11 @@ extern "C" {
 
     fn usvfsLogLevelToString(lv: LogLevel) -> *const i8;
     fn usvfsCrashDumpTypeToString(t: CrashDumpsType) -> *const i8;
+}
 
+#[cfg_attr(target_arch = "x86_64", link(name = "usvfs_x64"))]
+#[cfg_attr(target_arch = "x86", link(name = "usvfs_x86"))]
+extern "system" {
     fn usvfsClearVirtualMappings();
     fn usvfsVirtualLinkFile(source: *const u16, destination: *const u16, flags: u32) -> bool;
     fn usvfsVirtualLinkDirectoryStatic(
@@ -413,13 +1988,13 @@ extern "C" {
         destination: *const u16,
         flags: u32,
     ) -> bool;
-    fn usvfsConnectVfs(p: *const Parameters) -> bool;
+    fn usvfsConnectVFS(p: *const Parameters) -> bool;
     fn usvfsCreateVFS(p: *const Parameters) -> bool;
     fn usvfsDisconnectVFS();
     fn usvfsGetCurrentVFSName(buffer: *mut u8, size: size_t);
     /// unsafe
     pub fn usvfsGetVFSProcessList(count: *mut size_t, processIDs: *mut u32) -> bool;
-    /// unsafe
+    /// unsafe, the buffer is allocated by usvfs and must be released with free()
     pub fn usvfsGetVFSProcessList2(cont: *mut size_t, buffer: *mut *mut u32) -> bool;
     fn usvfsCreateProcessHooked(
         lpApplicationName: *const u16,
@@ -433,7 +2008,7 @@ extern "C" {
         lpStartupInfo: *mut STARTUPINFOW,
         lpProcessInformation: *mut PROCESS_INFORMATION,
     ) -> bool;
-    fn usvfsGetLogMessage(buffer: *mut u8, size: &mut size_t, blocking: bool) -> bool;
+    fn usvfsGetLogMessages(buffer: *mut u8, size: size_t, blocking: bool) -> bool;
     fn usvfsCreateVFSDump(buffer: *mut u8, size: *mut size_t) -> bool;
     fn usvfsBlacklistExecutable(executableName: *mut u16);
     fn usvfsClearExecutableBlacklist();
@@ -453,6 +2028,31 @@ extern "C" {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::os::windows::io::AsRawHandle;
+
+    /// serializes the tests that touch process wide state: the connection
+    /// to a vfs, its mappings and the global rule lists. cargo test runs
+    /// tests in parallel, so without it they'd change that state under
+    /// each other
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    /// take TEST_LOCK for the rest of the test. A test that failed while
+    /// holding it poisons it, which doesn't concern the others
+    pub(crate) fn test_lock() -> MutexGuard<'static, ()> {
+        lock_rules(&TEST_LOCK)
+    }
+
+    /// take TEST_LOCK and create a VFS for the test called name. Bind both,
+    /// `let (_lock, vfs) = test_vfs(..)`, so the VFS is dropped before the lock
+    pub(crate) fn test_vfs(name: &str) -> (MutexGuard<'static, ()>, Vfs) {
+        let lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name(name)
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        (lock, Vfs::create(params).expect("Failed to create VFS"))
+    }
 
     #[test]
     fn rawBindings() {
@@ -541,30 +2141,1998 @@ mod tests {
         assert_eq!(full.to_string(), "full");
     }
 
+    #[test]
+    fn parseRoundTrip() {
+        for level in [
+            LogLevel::Debug,
+            LogLevel::Info,
+            LogLevel::Warning,
+            LogLevel::Error,
+        ] {
+            assert_eq!(level.to_string().parse(), Ok(level));
+        }
+        for dump_type in [
+            CrashDumpsType::Nil,
+            CrashDumpsType::Mini,
+            CrashDumpsType::Data,
+            CrashDumpsType::Full,
+        ] {
+            assert_eq!(dump_type.to_string().parse(), Ok(dump_type));
+        }
+
+        assert_eq!("Warning".parse(), Ok(LogLevel::Warning));
+        assert_eq!(
+            "verbose".parse::<LogLevel>(),
+            Err(UsvfsError::ParseFailed {
+                kind: "log level",
+                input: "verbose".to_owned(),
+            })
+        );
+    }
+
     #[test]
     fn parameters() {
-        let testParams = Parameters::new();
-        testParams.set_instance_name("testInstance");
+        let mut testParams = ParametersHandle::new();
+        testParams
+            .set_instance_name("testInstance")
+            .expect("Failed to set instance name");
         testParams.set_debug_mode(false);
         testParams.set_log_level(LogLevel::Debug);
         testParams.set_crash_dumps_type(CrashDumpsType::Full);
-        testParams.set_crash_dumps_path("");
+        testParams
+            .set_crash_dumps_path("")
+            .expect("Failed to set crash dumps path");
         testParams.set_process_delay(time::Duration::new(1, 0));
-        testParams.free_parameters();
+    }
+
+    #[test]
+    fn instanceNameTooLong() {
+        let longest = "a".repeat(MAX_INSTANCE_NAME_LEN);
+        assert!(ParametersHandle::new().set_instance_name(&longest).is_ok());
+        // counted in bytes, so a multi-byte character can push it over
+        assert!(matches!(
+            ParametersHandle::builder()
+                .instance_name(&format!("{}é", &longest[1..]))
+                .build(),
+            Err(UsvfsError::NameTooLong { length: 65 })
+        ));
+    }
+
+    #[test]
+    fn generateInstanceName() {
+        let first = generate_instance_name("modManager");
+        let second = generate_instance_name("modManager");
+        assert_ne!(first, second);
+        for name in [&first, &second] {
+            assert!(name.starts_with("modManager-"));
+            assert!(name.len() <= MAX_INSTANCE_NAME_LEN);
+        }
+
+        let long = generate_instance_name(&"é".repeat(MAX_INSTANCE_NAME_LEN));
+        assert!(long.len() <= MAX_INSTANCE_NAME_LEN);
+        assert!(long.starts_with('é'));
+        assert!(ParametersHandle::builder()
+            .instance_name(&long)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn interiorNul() {
+        let mut testParams = ParametersHandle::new();
+        assert_eq!(
+            testParams.set_instance_name("bad\0name"),
+            Err(UsvfsError::InteriorNul)
+        );
+        assert_eq!(
+            testParams.set_crash_dumps_path("dumps\0"),
+            Err(UsvfsError::InteriorNul)
+        );
+    }
+
+    #[test]
+    fn processDelayOverflow() {
+        let huge = time::Duration::from_secs(u64::MAX / 1000 + 1);
+        assert_eq!(process_delay_millis(huge), i32::MAX);
+        assert_eq!(process_delay_millis(MAX_PROCESS_DELAY), i32::MAX);
+        assert_eq!(
+            process_delay_millis(time::Duration::from_millis(1500)),
+            1500
+        );
+
+        let mut testParams = ParametersHandle::new();
+        testParams.set_process_delay(huge);
+
+        // the largest delay usvfs can hold is kept exactly, one more
+        // millisecond is capped to it
+        let largest = time::Duration::from_millis(i32::MAX as u64);
+        assert_eq!(largest, MAX_PROCESS_DELAY);
+        testParams.set_process_delay(largest);
+        assert_eq!(testParams.process_delay(), largest);
+        let oneMore = largest + time::Duration::from_millis(1);
+        assert_eq!(process_delay_millis(oneMore), i32::MAX);
+        testParams.set_process_delay(oneMore);
+        assert_eq!(testParams.process_delay(), MAX_PROCESS_DELAY);
+        assert_eq!(
+            process_delay_millis(largest - time::Duration::from_millis(1)),
+            i32::MAX - 1
+        );
+    }
+
+    #[test]
+    fn parametersDropped() {
+        let mut testParams = ParametersHandle::new();
+        testParams
+            .set_instance_name("dropInstance")
+            .expect("Failed to set instance name");
+        testParams.set_log_level(LogLevel::Warning);
+        drop(testParams);
+    }
+
+    #[test]
+    fn parametersBuilder() {
+        ParametersHandle::builder()
+            .instance_name("builderInstance")
+            .debug_mode(false)
+            .log_level(LogLevel::Debug)
+            .crash_dumps_type(CrashDumpsType::Full)
+            .crash_dumps_path("")
+            .process_delay(time::Duration::new(1, 0))
+            .build()
+            .expect("Failed to build parameters");
+
+        assert_eq!(
+            ParametersBuilder::new().build().err(),
+            Some(UsvfsError::EmptyInstanceName)
+        );
+        assert_eq!(
+            ParametersBuilder::new()
+                .instance_name("builderInstance")
+                .crash_dumps_path("dumps\0")
+                .build()
+                .err(),
+            Some(UsvfsError::InteriorNul)
+        );
+    }
+
+    #[test]
+    fn linkFlags() {
+        let flags = LinkFlags::FAIL_IF_EXISTS | LinkFlags::RECURSIVE;
+        assert_eq!(flags.bits(), 0x9);
+        assert_eq!(flags.bits(), LINKFLAG_FAILIFEXISTS | LINKFLAG_RECURSIVE);
+        assert_eq!(LinkFlags::from_bits_truncate(0x9), flags);
+    }
+
+    #[test]
+    fn linkFlagsValidation() {
+        let file_flags = LinkFlags::FAIL_IF_EXISTS | LinkFlags::FAIL_IF_SKIPPED;
+        assert_eq!(file_flags.validate_for(MappingKind::File), Ok(()));
+        assert_eq!(
+            LinkFlags::all().validate_for(MappingKind::DirectoryStatic),
+            Ok(())
+        );
+        assert_eq!(
+            (LinkFlags::FAIL_IF_EXISTS | LinkFlags::CREATE_TARGET).validate_for(MappingKind::File),
+            Err(UsvfsError::InvalidFlags {
+                flags: LinkFlags::CREATE_TARGET,
+                kind: MappingKind::File,
+            })
+        );
+        assert_eq!(
+            LinkFlags::from_bits_retain(0x100).validate_for(MappingKind::DirectoryStatic),
+            Err(UsvfsError::InvalidFlags {
+                flags: LinkFlags::from_bits_retain(0x100),
+                kind: MappingKind::DirectoryStatic,
+            })
+        );
+
+        // rejected before usvfs is called, so no VFS is needed
+        let source = std::env::current_exe().expect("Failed to get current exe");
+        assert_eq!(
+            virtually_link_file(&source, "C:\\game\\test.exe", LinkFlags::RECURSIVE),
+            Err(UsvfsError::InvalidFlags {
+                flags: LinkFlags::RECURSIVE,
+                kind: MappingKind::File,
+            })
+        );
+        assert!(DryRunVfs::new()
+            .link_file(&source, "C:\\game\\test.exe", LinkFlags::MONITOR_CHANGES)
+            .is_err());
+    }
+
+    #[test]
+    fn errorDisplay() {
+        let error = UsvfsError::LinkFailed {
+            source: "C:\\mods\\a.esp".to_owned(),
+            destination: "C:\\game\\a.esp".to_owned(),
+            code: 2,
+        };
+        assert_eq!(error.code(), Some(2));
+        assert_eq!(
+            error.to_string(),
+            "failed to link C:\\mods\\a.esp to C:\\game\\a.esp (win32 error 2)"
+        );
+        assert_eq!(UsvfsError::InteriorNul.code(), None);
+    }
+
+    #[test]
+    fn processInfosEmpty() {
+        let _lock = test_lock();
+        disconnect_vfs();
+        assert_eq!(vfs_process_infos(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn processListEmpty() {
+        let _lock = test_lock();
+        disconnect_vfs();
+        assert_eq!(vfs_process_list(), Ok(Vec::new()));
+        assert_eq!(vfs_process_list_alloc(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn configApply() {
+        let _lock = test_lock();
+        let config = VfsConfig {
+            instance_name: "configInstance".to_owned(),
+            debug_mode: false,
+            log_level: LogLevel::Info,
+            crash_dumps_type: CrashDumpsType::Mini,
+            crash_dumps_path: PathBuf::new(),
+            process_delay_ms: 250,
+        };
+        let mut testParams = ParametersHandle::new();
+        config
+            .apply_to(&mut testParams)
+            .expect("Failed to apply config");
+
+        create_vfs(&testParams).expect("Failed to create VFS");
+        assert!(current_vfs_name().is_some_and(|name| name.contains("configInstance")));
+        disconnect_vfs();
+    }
+
+    #[test]
+    fn configIntoParameters() {
+        let _lock = test_lock();
+        let config = VfsConfig {
+            instance_name: "configIntoTest".to_owned(),
+            debug_mode: true,
+            log_level: LogLevel::Warning,
+            crash_dumps_type: CrashDumpsType::Data,
+            crash_dumps_path: std::env::temp_dir(),
+            process_delay_ms: 250,
+        };
+        let params = ParametersHandle::try_from(config.clone()).expect("Failed to convert config");
+        assert_eq!(params.instance_name(), "configIntoTest");
+        assert!(params.debug_mode());
+        assert_eq!(params.log_level(), LogLevel::Warning);
+        assert_eq!(params.crash_dumps_type(), CrashDumpsType::Data);
+        assert_eq!(params.crash_dumps_path(), std::env::temp_dir());
+        assert_eq!(params.process_delay(), time::Duration::from_millis(250));
+
+        let _vfs = Vfs::create(params).expect("Failed to create VFS");
+        assert!(current_vfs_name().is_some_and(|name| name.contains("configIntoTest")));
+
+        let invalid = VfsConfig {
+            instance_name: "x".repeat(MAX_INSTANCE_NAME_LEN + 1),
+            ..config.clone()
+        };
+        assert_eq!(
+            ParametersHandle::try_from(invalid).err(),
+            Some(UsvfsError::NameTooLong {
+                length: MAX_INSTANCE_NAME_LEN + 1
+            })
+        );
+        let invalid = VfsConfig {
+            crash_dumps_path: PathBuf::from("dumps\0"),
+            ..config
+        };
+        assert_eq!(
+            ParametersHandle::try_from(invalid).err(),
+            Some(UsvfsError::InteriorNul)
+        );
+    }
+
+    #[test]
+    fn configDefault() {
+        let config = VfsConfig::default();
+        assert_eq!(config.instance_name, "");
+        assert!(!config.debug_mode);
+        assert_eq!(config.log_level, LogLevel::Info);
+        assert_eq!(config.crash_dumps_type, CrashDumpsType::Nil);
+        assert_eq!(config.crash_dumps_path, PathBuf::new());
+        assert_eq!(config.process_delay_ms, 0);
+
+        let mut testParams = ParametersHandle::new();
+        assert_eq!(config.apply_to(&mut testParams), Ok(()));
+        assert_eq!(testParams.log_level(), LogLevel::Info);
     }
 
     #[test]
     fn startAndStop() {
-        let testParams = Parameters::new();
-        testParams.set_instance_name("test");
+        let _lock = test_lock();
+        let mut testParams = ParametersHandle::new();
+        testParams
+            .set_instance_name("test")
+            .expect("Failed to set instance name");
         testParams.set_debug_mode(false);
         testParams.set_log_level(LogLevel::Debug);
         testParams.set_crash_dumps_type(CrashDumpsType::Nil);
-        testParams.set_crash_dumps_path("");
+        testParams
+            .set_crash_dumps_path("")
+            .expect("Failed to set crash dumps path");
+
+        match try_init_logging(false) {
+            Ok(()) | Err(UsvfsError::LoggingAlreadyInitialized) => {}
+            Err(error) => panic!("Failed to initialize logging: {error}"),
+        }
+        assert_eq!(
+            try_init_logging(false),
+            Err(UsvfsError::LoggingAlreadyInitialized)
+        );
+        create_vfs(&testParams).expect("Failed to create VFS");
+        assert!(current_vfs_name().is_some_and(|name| name.contains("test")));
+        disconnect_vfs();
+        // drains whatever was logged and must terminate once the queue is empty
+        drain_log_messages().for_each(drop);
+    }
+
+    #[test]
+    fn narrowNonAscii() {
+        let text = "C:\\Spiele\\Ünïcödé Mød\\データ\\файл.esp";
+        let mut buffer = text.as_bytes().to_vec();
+        buffer.extend([0x00, 0xFF, 0xFF]);
+        assert_eq!(narrow_to_string(&buffer), text);
+
+        // an ANSI é and a character cut in half don't panic
+        assert_eq!(narrow_to_string(b"caf\xE9\x00"), "caf\u{FFFD}");
+        assert_eq!(narrow_to_string(&"データ".as_bytes()[..7]), "デー\u{FFFD}");
+    }
+
+    #[test]
+    fn widenNonAscii() {
+        let path = "C:\\Spiele\\Ünïcödé Mød\\データ\\файл.esp";
+        let wide = widen(path);
+        assert_eq!(wide.last(), Some(&0x00));
+        assert_eq!(
+            String::from_utf16(&wide[..wide.len() - 1]).expect("Invalid Utf16"),
+            path
+        );
+    }
+
+    #[test]
+    fn widenLongPath() {
+        let decode = |wide: Vec<u16>| String::from_utf16_lossy(&wide[..wide.len() - 1]);
+        let deep = "very deep mod directory\\".repeat(12) + "plugin.esp";
+
+        let drive = format!("C:\\games\\{deep}");
+        assert!(drive.len() > MAX_PATH as usize);
+        assert_eq!(
+            decode(widen_path(Path::new(&drive))),
+            format!("\\\\?\\{drive}")
+        );
+        let unc = format!("\\\\server\\share\\{deep}");
+        assert_eq!(
+            decode(widen_path(Path::new(&unc))),
+            format!("\\\\?\\UNC\\server\\share\\{deep}")
+        );
+        let slashes = drive.replace('\\', "/");
+        assert_eq!(
+            decode(widen_path(Path::new(&slashes))),
+            format!("\\\\?\\{drive}")
+        );
+
+        // already prefixed, relative, unresolved and short paths stay as they are
+        for unchanged in [
+            format!("\\\\?\\{drive}"),
+            deep.clone(),
+            format!("C:\\games\\..\\{deep}"),
+            "C:\\games\\plugin.esp".to_owned(),
+        ] {
+            assert_eq!(widen_path(Path::new(&unchanged)), widen(&unchanged));
+        }
+    }
+
+    #[test]
+    fn linkLongNonAsciiPath() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs ünïcödé テスト");
+        let long_name = "длинное имя файла ".repeat(8) + "データ.esp";
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join(&long_name);
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+        let destination = dir.join(format!("linked {}", long_name));
+
+        let mut testParams = ParametersHandle::new();
+        testParams
+            .set_instance_name("linkTest")
+            .expect("Failed to set instance name");
+        testParams.set_debug_mode(false);
+        testParams.set_log_level(LogLevel::Debug);
+        testParams.set_crash_dumps_type(CrashDumpsType::Nil);
+        testParams
+            .set_crash_dumps_path("")
+            .expect("Failed to set crash dumps path");
+
+        create_vfs(&testParams).expect("Failed to create VFS");
+        let linked = virtually_link_file(&source, &destination, LinkFlags::empty());
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        linked.expect("Failed to link file");
+    }
+
+    #[test]
+    fn duplicateParameters() {
+        let _lock = test_lock();
+        let original = ParametersHandle::builder()
+            .instance_name("dupeOriginal")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        let mut copy = original.duplicate();
+        copy.set_instance_name("dupeCopy")
+            .expect("Failed to set instance name");
+
+        let mut target = ParametersHandle::new();
+        original.copy_into(&mut target);
+        drop(copy);
+
+        create_vfs(&original).expect("Failed to create VFS");
+        let originalName = current_vfs_name();
+        create_vfs(&target).expect("Failed to create VFS");
+        let targetName = current_vfs_name();
+        disconnect_vfs();
+
+        assert!(originalName.is_some_and(|name| name.contains("dupeOriginal")));
+        assert!(targetName.is_some_and(|name| name.contains("dupeOriginal")));
+    }
+
+    #[test]
+    fn cloneParameters() {
+        let _lock = test_lock();
+        let template = ParametersHandle::builder()
+            .instance_name("cloneTemplate")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        let mut clone = template.clone();
+        clone
+            .set_instance_name("cloneCopy")
+            .expect("Failed to set instance name");
+
+        create_vfs(&clone).expect("Failed to create VFS");
+        let cloneName = current_vfs_name();
+        create_vfs(&template).expect("Failed to create VFS");
+        let templateName = current_vfs_name();
+        disconnect_vfs();
+        drop(clone);
+        drop(template);
+
+        assert!(cloneName.is_some_and(|name| name.contains("cloneCopy")));
+        assert!(templateName.is_some_and(|name| name.contains("cloneTemplate")));
+    }
+
+    #[test]
+    fn updateLive() {
+        let _lock = test_lock();
+        let mut testParams = ParametersHandle::builder()
+            .instance_name("updateTest")
+            .log_level(LogLevel::Warning)
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+
+        create_vfs(&testParams).expect("Failed to create VFS");
+        testParams.set_log_level(LogLevel::Debug);
+        testParams.update();
+        disconnect_vfs();
+    }
+
+    #[test]
+    fn sessionDisconnectsOnDrop() {
+        let dir = std::env::temp_dir().join("usvfs-rs-session");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+
+        let (_lock, mut vfs) = test_vfs("sessionTest");
+        vfs.link_file(&source, dir.join("destination.esp"), LinkFlags::empty())
+            .expect("Failed to link file");
+        vfs.clear_mappings();
+        assert!(is_connected());
+        drop(vfs);
+
+        // the session disconnected on drop, both as far as this crate and
+        // usvfs know, and a fresh session can take its place
+        assert!(!is_connected());
+        assert_eq!(current_vfs_name(), None);
+        let params = ParametersHandle::builder()
+            .instance_name("sessionTest2")
+            .build()
+            .expect("Failed to build parameters");
+        drop(Vfs::create(params).expect("Failed to create VFS"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn spawnHooked() {
+        let (_lock, _vfs) = test_vfs("spawnTest");
+
+        let process = spawn_hooked(
+            Path::new("C:\\Windows\\System32\\cmd.exe"),
+            "cmd.exe /c exit 3",
+        )
+        .expect("Failed to spawn hooked process");
+        assert_ne!(process.pid(), 0);
+        assert_eq!(process.wait(), Ok(3));
+    }
+
+    #[test]
+    fn waitForAllProcesses() {
+        let (_lock, _vfs) = test_vfs("waitAllTest");
+
+        let _process = spawn_hooked(
+            Path::new("C:\\Windows\\System32\\cmd.exe"),
+            "cmd.exe /c ping -n 2 127.0.0.1 >nul",
+        )
+        .expect("Failed to spawn hooked process");
+        assert_eq!(
+            wait_for_all_processes(Some(time::Duration::ZERO)),
+            Err(UsvfsError::TimedOut)
+        );
+        assert_eq!(
+            wait_for_all_processes(Some(time::Duration::from_secs(30))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn waitForPid() {
+        let (_lock, _vfs) = test_vfs("waitPidTest");
+
+        // the HookedProcess holds a handle, so the pid stays valid after exit
+        let process = spawn_hooked(
+            Path::new("C:\\Windows\\System32\\cmd.exe"),
+            "cmd.exe /c ping -n 2 127.0.0.1 >nul & exit 4",
+        )
+        .expect("Failed to spawn hooked process");
+        assert_eq!(
+            wait_for_pid(process.pid(), Some(time::Duration::ZERO)),
+            Ok(None)
+        );
+        assert_eq!(
+            wait_for_pid(process.pid(), Some(time::Duration::from_secs(30))),
+            Ok(Some(4))
+        );
+        assert_eq!(wait_for_pid(process.pid(), None), Ok(Some(4)));
+
+        // pids are multiples of 4, so this one is never used
+        assert_eq!(
+            wait_for_pid(0xFFFF_FFF1, None),
+            Err(UsvfsError::NoSuchProcess { pid: 0xFFFF_FFF1 })
+        );
+    }
+
+    #[test]
+    fn sessionIsSend() {
+        fn assertSend<T: Send>() {}
+        assertSend::<ParametersHandle>();
+        assertSend::<Vfs>();
+    }
+
+    #[test]
+    fn blacklistGuard() {
+        let _lock = test_lock();
+        clear_executable_blacklist();
+        blacklist_executable("launcher.exe");
+        {
+            let _guard = BlacklistGuard::new(&["helper.exe", "crashreporter.exe"]);
+            assert_eq!(
+                blacklisted_executables(),
+                ["launcher.exe", "helper.exe", "crashreporter.exe"]
+            );
+        }
+        assert_eq!(blacklisted_executables(), ["launcher.exe"]);
+        clear_executable_blacklist();
+    }
+
+    #[test]
+    fn forceLoadGuard() {
+        let _lock = test_lock();
+        let library = std::env::temp_dir().join("usvfs-rs-force-load.dll");
+        std::fs::write(&library, b"").expect("Failed to create test library");
+        let library = library.to_string_lossy().into_owned();
+
+        clear_library_force_loads();
+        force_load_library("outer.exe", &library);
+        {
+            let guard = ForceLoadGuard::new(&[("game.exe", library.as_str())])
+                .expect("Failed to register force load");
+            assert_eq!(guard.previous().len(), 1);
+            assert_eq!(forced_libraries().len(), 2);
+        }
+        let restored = forced_libraries();
+        let missing = ForceLoadGuard::new(&[("game.exe", "C:\\usvfs-rs\\missing.dll")]);
+        let afterMissing = forced_libraries();
+        clear_library_force_loads();
+        std::fs::remove_file(&library).ok();
+
+        assert_eq!(restored, vec![("outer.exe".to_owned(), library)]);
+        assert!(matches!(
+            missing,
+            Err(UsvfsError::Io {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            })
+        ));
+        assert_eq!(afterMissing, restored);
+    }
+
+    #[test]
+    fn skipDirsGuard() {
+        let _lock = test_lock();
+        clear_skip_directories();
+        {
+            let _guard = SkipDirsGuard::new(&[".git", "node_modules"]);
+            assert_eq!(skipped_directories(), [".git", "node_modules"]);
+        }
+        assert!(skipped_directories().is_empty());
+    }
+
+    #[test]
+    fn skipFileSuffixes() {
+        let _lock = test_lock();
+        clear_skip_file_suffixes();
+        add_skip_file_suffix(".txt");
+        add_skip_file_suffix("some_file.txt");
+        assert_eq!(skip_file_suffixes(), [".txt", "some_file.txt"]);
+        clear_skip_file_suffixes();
+        assert!(skip_file_suffixes().is_empty());
+    }
+
+    #[test]
+    fn addRulesBatch() {
+        let (_lock, _vfs) = test_vfs("rulesBatchTest");
+        clear_skip_directories();
+        clear_executable_blacklist();
+
+        assert_eq!(
+            add_skip_directories(&[".git", "bro\0ken", "also\0broken"]),
+            Err(UsvfsError::InteriorNulAt { index: 1 })
+        );
+        assert!(skipped_directories().is_empty());
+        assert_eq!(add_skip_directories(&[".git", ".svn"]), Ok(()));
+        assert_eq!(skipped_directories(), [".git", ".svn"]);
+        assert_eq!(
+            blacklist_executables(&["\0"]),
+            Err(UsvfsError::InteriorNulAt { index: 0 })
+        );
+        assert_eq!(blacklist_executables(&["launcher.exe"]), Ok(()));
+        assert_eq!(blacklisted_executables(), ["launcher.exe"]);
+
+        clear_skip_directories();
+        clear_executable_blacklist();
+    }
+
+    #[test]
+    fn linkFilesBatch() {
+        let dir = std::env::temp_dir().join("usvfs-rs-batch");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+        // the destination directory has to exist, at least virtually
+        let missing = dir.join("missing").join("nested").join("broken.esp");
+        let entries = [
+            (source.clone(), dir.join("first.esp"), LinkFlags::empty()),
+            (source.clone(), missing, LinkFlags::empty()),
+            (source.clone(), dir.join("third.esp"), LinkFlags::empty()),
+        ];
+
+        let (_lock, vfs) = test_vfs("batchTest");
+        let all = link_files(&entries);
+        let stopped = link_files_stop_on_error(&entries);
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+
+        let failed: Vec<usize> = all.unwrap_err().iter().map(|(index, _)| *index).collect();
+        assert_eq!(failed, [1]);
+        assert_eq!(stopped.unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn manifest() {
+        let entries = parse_manifest(
+            "# a comment\n\
+             C:\\mods\\a\\plugin.esp\tC:\\game\\data\\plugin.esp\r\n\
+             \n\
+             C:\\mods\\b\\textures\tC:\\game\\data\\textures\tRECURSIVE | FAIL_IF_EXISTS\n",
+        )
+        .expect("Failed to parse manifest");
+        assert_eq!(
+            entries,
+            [
+                ManifestEntry {
+                    source: PathBuf::from("C:\\mods\\a\\plugin.esp"),
+                    destination: PathBuf::from("C:\\game\\data\\plugin.esp"),
+                    flags: LinkFlags::empty(),
+                },
+                ManifestEntry {
+                    source: PathBuf::from("C:\\mods\\b\\textures"),
+                    destination: PathBuf::from("C:\\game\\data\\textures"),
+                    flags: LinkFlags::RECURSIVE | LinkFlags::FAIL_IF_EXISTS,
+                },
+            ]
+        );
+
+        let malformed = parse_manifest("a\tb\nc\td\tNOT_A_FLAG\n");
+        assert!(matches!(
+            malformed,
+            Err(UsvfsError::ManifestInvalid { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_manifest("only one field"),
+            Err(UsvfsError::ManifestInvalid { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn parseDump() {
+        // sample in the format usvfs's dumpTree() writes
+        let dump = " -> \n \
+                     c: -> \n  \
+                      games -> \n   \
+                       data -> c:\\mods\\a\\data\n    \
+                        plugin.esp -> c:\\mods\\a\\data\\plugin.esp\n    \
+                        textures -> \n     \
+                         sky.dds -> c:\\mods\\b\\sky.dds\n  \
+                      saves -> c:\\profiles\\saves\n";
+        let tree = parse_vfs_dump(dump).expect("Failed to parse dump");
+
+        let drive = &tree.root.children[0];
+        assert_eq!(drive.name, "c:");
+        assert_eq!(drive.kind, NodeKind::Directory);
+        let games = &drive.children[0];
+        assert_eq!(games.virtual_path, PathBuf::from("c:\\games"));
+        assert_eq!(drive.children[1].name, "saves");
+        assert_eq!(drive.children[1].kind, NodeKind::File);
+
+        let data = &games.children[0];
+        assert_eq!(data.source, Some(PathBuf::from("c:\\mods\\a\\data")));
+        let plugin = &data.children[0];
+        assert_eq!(plugin.kind, NodeKind::File);
+        assert_eq!(
+            plugin.virtual_path,
+            PathBuf::from("c:\\games\\data\\plugin.esp")
+        );
+        let sky = &data.children[1].children[0];
+        assert_eq!(sky.source, Some(PathBuf::from("c:\\mods\\b\\sky.dds")));
+
+        assert_eq!(
+            parse_vfs_dump(" -> \n   too deep -> \n").unwrap_err().line,
+            2
+        );
+        assert_eq!(parse_vfs_dump("no arrow\n").unwrap_err().line, 1);
+
+        assert_eq!(tree.find("C:\\Games/Data\\plugin.esp"), Some(plugin));
+        assert_eq!(tree.find("c:\\games\\missing.esp"), None);
+
+        let names: Vec<&str> = tree.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "",
+                "c:",
+                "games",
+                "data",
+                "plugin.esp",
+                "textures",
+                "sky.dds",
+                "saves"
+            ]
+        );
+        let files: Vec<&str> = tree.iter_files().map(|node| node.name.as_str()).collect();
+        assert_eq!(files, ["plugin.esp", "sky.dds", "saves"]);
+        let directories: Vec<&str> = tree
+            .iter_directories()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(directories, ["", "c:", "games", "data", "textures"]);
+    }
+
+    #[test]
+    fn dumpStats() {
+        let dump = " -> \n \
+                     c: -> \n  \
+                      games -> \n   \
+                       data -> c:\\mods\\a\\data\n    \
+                        plugin.esp -> c:\\mods\\a\\data\\plugin.esp\n    \
+                        textures -> \n     \
+                         sky.dds -> c:\\mods\\b\\sky.dds\n  \
+                      saves -> c:\\profiles\\saves\n";
+        let expected = DumpStats {
+            files: 3,
+            directories: 4,
+            redirected: 4,
+        };
+        assert_eq!(count_dump_nodes(dump), expected);
+
+        // tabs, CRLF, missing spaces around the marker and stray lines
+        // still count the same
+        let variant = "->\r\n\tc:->\r\n\t\tgames ->\r\n\t\t\tdata ->c:\\mods\\a\\data\r\n\
+                       \t\t\t\tplugin.esp-> c:\\mods\\a\\data\\plugin.esp\r\n\
+                       mapping tree:\r\n\
+                       \t\t\t\ttextures ->\r\n\t\t\t\t\tsky.dds -> c:\\mods\\b\\sky.dds\r\n\
+                       \t\tsaves -> c:\\profiles\\saves\r\n";
+        assert_eq!(count_dump_nodes(variant), expected);
+        assert_eq!(count_dump_nodes(""), DumpStats::default());
+
+        let (_lock, _vfs) = test_vfs("dumpStatsTest");
+        assert!(dump_stats().is_ok());
+    }
+
+    #[test]
+    fn debugInfo() {
+        let (_lock, _vfs) = test_vfs("debugInfoTest");
+        print_debug_info();
+    }
+
+    #[test]
+    fn reconnectKeepsMappings() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-reconnect");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+
+        let params = ParametersHandle::builder()
+            .instance_name("reconnectTest")
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        virtually_link_file(&source, dir.join("survivor.esp"), LinkFlags::empty())
+            .expect("Failed to link file");
+        let reconnected = reconnect(&params);
+        let dump = vfs_dump();
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        reconnected.expect("Failed to reconnect");
+        assert!(dump
+            .expect("Failed to dump VFS")
+            .to_lowercase()
+            .contains("survivor.esp"));
+    }
+
+    #[test]
+    fn sessionTracksMappings() {
+        let dir = std::env::temp_dir().join("usvfs-rs-tracking");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+
+        let (_lock, mut vfs) = test_vfs("trackingTest");
+        let linked = ["a.esp", "b.esp", "c.esp"]
+            .iter()
+            .filter(|name| {
+                vfs.link_file(&source, dir.join(name), LinkFlags::empty())
+                    .is_ok()
+            })
+            .count();
+        let count = vfs.mapping_count();
+        let first = vfs.mappings().first().cloned();
+        vfs.clear_mappings();
+        let cleared = vfs.mapping_count();
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(count, linked);
+        assert_eq!(
+            first.map(|mapping| (mapping.destination, mapping.kind)),
+            Some((dir.join("a.esp"), MappingKind::File))
+        );
+        assert_eq!(cleared, 0);
+    }
+
+    #[test]
+    fn dumpContainsLink() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-dump");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+        let destination = dir.join("destination.esp");
+
+        let testParams = ParametersHandle::builder()
+            .instance_name("dumpTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+
+        create_vfs(&testParams).expect("Failed to create VFS");
+        virtually_link_file(&source, &destination, LinkFlags::empty())
+            .expect("Failed to link file");
+        let size = vfs_dump_size();
+        let mut probed = 0;
+        let probe = create_vfs_dump(None, &mut probed);
+        let mut raw = vec![0u8; probed + 1];
+        let mut rawSize = raw.len();
+        let filled = create_vfs_dump(Some(&mut raw), &mut rawSize);
+        let dump = vfs_dump();
+        let mut written = Vec::new();
+        let written = dump_to_writer(&mut written).map(|()| written);
+        let file = File::create(dir.join("dump.txt")).expect("Failed to create dump file");
+        let toHandle = dump_to_handle(HANDLE(file.as_raw_handle()));
+        drop(file);
+        let fromHandle = std::fs::read_to_string(dir.join("dump.txt"));
+        let invalidHandle = dump_to_handle(HANDLE::default());
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let dump = dump.expect("Failed to dump VFS");
+        assert_eq!(size, Ok(dump.len()));
+        assert_eq!(
+            probe,
+            Err(UsvfsError::DumpFailed {
+                code: ERROR_MORE_DATA.0
+            })
+        );
+        assert_eq!(probed, dump.len());
+        assert_eq!(filled, Ok(()));
+        assert_eq!(rawSize, dump.len());
+        assert_eq!(narrow_to_string(&raw), dump);
+        let dump = dump.to_lowercase();
+        assert!(dump.contains("destination.esp"));
+        let written = String::from_utf8(written.expect("Failed to write dump"))
+            .expect("Dump is not UTF-8")
+            .to_lowercase();
+        assert_eq!(written, dump);
+        assert_eq!(toHandle, Ok(()));
+        let fromHandle = fromHandle.expect("Failed to read dump file").to_lowercase();
+        assert_eq!(fromHandle, dump);
+        assert!(matches!(
+            invalidHandle,
+            Err(UsvfsError::Io {
+                kind: std::io::ErrorKind::InvalidInput,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn logMessageParse() {
+        let message =
+            LogMessage::parse("12:34:56.789 <1234:5678> [W] failed to hook CreateFileW\n");
+        assert_eq!(message.level, LogLevel::Warning);
+        assert_eq!(message.text, "failed to hook CreateFileW");
+
+        let message = LogMessage::parse("12:34:56.789 <1234:5678> [E] ");
+        assert_eq!(message.level, LogLevel::Error);
+        assert_eq!(message.text, "");
+
+        let message = LogMessage::parse("not a usvfs line");
+        assert_eq!(message.level, LogLevel::Info);
+        assert_eq!(message.text, "not a usvfs line");
+        assert_eq!(message.time_of_day, None);
+        assert_eq!(message.timestamp, None);
+    }
+
+    #[test]
+    fn logMessageTime() {
+        let message = LogMessage::parse("12:34:56.789 <1234:5678> [I] mapped plugin.esp");
+        let timeOfDay = time::Duration::from_millis(((12 * 60 + 34) * 60 + 56) * 1000 + 789);
+        assert_eq!(message.time_of_day, Some(timeOfDay));
+        assert_eq!(message.text, "mapped plugin.esp");
+
+        // a malformed time still gives the level and text
+        for time in ["24:00:00.000", "12:34:56", "1:34:56.789", "12:34:56.78x"] {
+            let message = LogMessage::parse(&format!("{time} <1234:5678> [W] text"));
+            assert_eq!(message.time_of_day, None, "{time}");
+            assert_eq!(message.timestamp, None, "{time}");
+            assert_eq!(message.level, LogLevel::Warning);
+        }
+
+        #[cfg(not(feature = "chrono"))]
+        assert_eq!(message.timestamp, None);
+        #[cfg(feature = "chrono")]
+        {
+            use chrono::{Local, NaiveTime, Timelike};
+            // the last time the clock showed 12:34:56.789, within a day
+            let timestamp = message.timestamp.expect("Failed to get timestamp");
+            let age = time::SystemTime::now()
+                .duration_since(timestamp)
+                .expect("Timestamp is in the future");
+            assert!(age < time::Duration::from_secs(25 * 60 * 60));
+            let logged = chrono::DateTime::<Local>::from(timestamp).time();
+            assert_eq!(
+                logged.with_nanosecond(0),
+                NaiveTime::from_hms_opt(12, 34, 56)
+            );
+            assert_eq!(logged.nanosecond(), 789_000_000);
+        }
+    }
+
+    #[test]
+    fn logPumpStops() {
+        let stop = std::sync::Arc::new(AtomicBool::new(false));
+        let (handle, receiver) = spawn_log_pump(stop.clone());
+        stop.store(true, Ordering::Relaxed);
+        handle.join().expect("Log pump panicked");
+        // the sender is dropped with the thread, so the channel closes
+        while receiver.try_recv().is_ok() {}
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn logMessageTimeout() {
+        // other tests may log in the meantime, so read until the queue
+        // stays empty for the whole timeout
+        let timeout = time::Duration::from_millis(100);
+        let timedOut = (0..100).find_map(|_| {
+            let start = time::Instant::now();
+            match next_log_message_timeout(timeout) {
+                Some(_) => None,
+                None => Some(start.elapsed()),
+            }
+        });
+        assert!(timedOut.is_some_and(|elapsed| elapsed >= timeout));
+    }
+
+    #[test]
+    fn linkWide() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-wide");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+        let destination = dir.join("destination.esp");
+
+        let testParams = ParametersHandle::builder()
+            .instance_name("wideTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&testParams).expect("Failed to create VFS");
+
+        // without a terminator, one is appended
+        let unterminated: Vec<u16> = destination.as_os_str().encode_wide().collect();
+        let linked = virtually_link_file_wide(&widen(&source), &unterminated, LinkFlags::empty());
+        let recorded = recorded_mappings();
+        let interior = virtually_link_file_wide(
+            &[0x61, 0x00, 0x62],
+            &widen(&destination),
+            LinkFlags::empty(),
+        );
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(linked, Ok(()));
+        assert_eq!(
+            recorded.last().map(|mapping| &mapping.destination),
+            Some(&destination)
+        );
+        assert_eq!(interior, Err(UsvfsError::InteriorNul));
+    }
+
+    #[test]
+    fn isMapped() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-mapped");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+        let destination = dir.join("destination.esp");
+
+        let testParams = ParametersHandle::builder()
+            .instance_name("mappedTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&testParams).expect("Failed to create VFS");
+        virtually_link_file(&source, &destination, LinkFlags::empty())
+            .expect("Failed to link file");
+        let mapped = is_mapped(&destination);
+        let sibling = is_mapped(dir.join("sibling.esp"));
+        let resolved = resolve(&destination);
+        let mixedCase = dir.join("Destination.ESP");
+        let resolvedMixedCase = resolve(&mixedCase);
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(mapped);
+        assert_eq!(resolvedMixedCase, resolved);
+        assert!(!sibling);
+        assert_eq!(
+            resolved.map(|path| path.to_string_lossy().to_lowercase()),
+            Some(source.to_string_lossy().to_lowercase())
+        );
+    }
+
+    #[test]
+    fn hookedCommand() {
+        let mut command = HookedCommand::new("C:\\Windows\\System32\\cmd.exe");
+        command.args(["/c", "exit 4"]);
+        let commandLine = command
+            .command_line()
+            .expect("Failed to build command line");
+        assert_eq!(
+            String::from_utf16_lossy(&commandLine),
+            "\"C:\\Windows\\System32\\cmd.exe\" /c \"exit 4\"\0"
+        );
+
+        let mut quoting = HookedCommand::new("app.exe");
+        quoting.args(["a\"b\\", "dir\\ x\\", ""]);
+        let quoted = quoting
+            .command_line()
+            .expect("Failed to build command line");
+        assert_eq!(
+            String::from_utf16_lossy(&quoted),
+            "\"app.exe\" a\\\"b\\ \"dir\\ x\\\\\" \"\"\0"
+        );
+        assert_eq!(
+            HookedCommand::new("app.exe").arg("a\0b").command_line(),
+            Err(UsvfsError::InteriorNul)
+        );
+
+        let (_lock, _vfs) = test_vfs("commandTest");
+        let process = command
+            .env("USVFS_RS_TEST", "1")
+            .spawn()
+            .expect("Failed to spawn hooked command");
+        assert_eq!(process.wait(), Ok(4));
+    }
+
+    #[test]
+    fn spawnMissingExecutable() {
+        let (_lock, _vfs) = test_vfs("missingExeTest");
+
+        let error = spawn_hooked(
+            Path::new("C:\\usvfs-rs\\does-not-exist.exe"),
+            "does-not-exist.exe",
+        )
+        .expect_err("Spawning a missing executable succeeded");
+        // ERROR_FILE_NOT_FOUND or ERROR_PATH_NOT_FOUND
+        assert!(
+            matches!(
+                error,
+                UsvfsError::ProcessSpawnFailed {
+                    win32_code: 2 | 3,
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+        assert!(matches!(error.code(), Some(2 | 3)));
+        assert!(!system_message(2).is_empty());
+    }
+
+    #[test]
+    fn panicDisconnects() {
+        let _lock = test_lock();
+        // a panic on another thread unwinds through the scope holding the session
+        let result = std::thread::spawn(|| {
+            let params = ParametersHandle::builder()
+                .instance_name("panicTest")
+                .crash_dumps_type(CrashDumpsType::Nil)
+                .build()
+                .expect("Failed to build parameters");
+            with_vfs(params, |_vfs| panic!("panicking while connected"))
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert_eq!(current_vfs_name(), None);
+    }
+
+    #[test]
+    fn disconnectOnPanic() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("panicHookTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+
+        // the hook body is tested on its own, installing the hook would
+        // disconnect whatever test panics next
+        let mut connectedInPrevious = None;
+        disconnect_then(|| connectedInPrevious = Some(is_connected()));
+
+        assert_eq!(connectedInPrevious, Some(false));
+        assert_eq!(current_vfs_name(), None);
+        let mut called = false;
+        disconnect_then(|| called = true);
+        assert!(called);
+    }
+
+    #[test]
+    fn logMessageTruncated() {
+        let prefix = "12:34:56.789 <1234:5678> [E] ";
+        let full = format!(
+            "{prefix}{}",
+            "x".repeat(LOG_MESSAGE_BUFFER_SIZE - 1 - prefix.len())
+        );
+        let message = LogMessage::parse(&full);
+        assert!(message.truncated);
+        assert_eq!(message.level, LogLevel::Error);
+
+        assert!(!LogMessage::parse(&full[..full.len() - 1]).truncated);
+    }
+
+    #[test]
+    fn globalRules() {
+        let (_lock, _vfs) = test_vfs("rulesTest");
+
+        blacklist_executable("stale.exe");
+        add_skip_directory("stale");
+        let rules = GlobalRules {
+            skip_file_suffixes: vec![".txt".to_owned()],
+            skip_directories: vec![".git".to_owned()],
+            blacklist: vec!["launcher.exe".to_owned()],
+            force_loads: Vec::new(),
+        };
+        rules.apply();
+        let blacklist = blacklisted_executables();
+        let skipped = skipped_directories();
+        let suffixes = skip_file_suffixes();
+        GlobalRules::default().apply();
+
+        assert_eq!(blacklist, rules.blacklist);
+        assert_eq!(skipped, rules.skip_directories);
+        assert_eq!(suffixes, rules.skip_file_suffixes);
+        assert!(blacklisted_executables().is_empty());
+    }
+
+    #[test]
+    fn globalRulesSnapshot() {
+        let (_lock, _vfs) = test_vfs("rulesSnapshotTest");
+
+        let original = GlobalRules {
+            skip_file_suffixes: vec![".txt".to_owned()],
+            skip_directories: vec![".git".to_owned()],
+            blacklist: vec!["launcher.exe".to_owned()],
+            force_loads: vec![("game.exe".to_owned(), "C:\\plugins\\hook.dll".to_owned())],
+        };
+        original.apply();
+        let snapshot = GlobalRules::capture();
+        assert_eq!(snapshot, original);
+
+        add_skip_file_suffix(".log");
+        clear_skip_directories();
+        blacklist_executable("crashreporter.exe");
+        clear_library_force_loads();
+        assert_ne!(GlobalRules::capture(), snapshot);
+
+        GlobalRules::restore(&snapshot);
+        let restored = GlobalRules::capture();
+        GlobalRules::default().apply();
+
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn createErrors() {
+        assert_eq!(create_error(183), UsvfsError::AlreadyExists);
+        assert_eq!(create_error(5), UsvfsError::AccessDenied);
+        assert_eq!(create_error(31), UsvfsError::CreateFailed { code: 31 });
+        assert_eq!(UsvfsError::AlreadyExists.code(), Some(183));
+    }
+
+    #[test]
+    fn envBlock() {
+        let decode = |block: &EnvBlock| String::from_utf16_lossy(&block.encode());
+
+        assert_eq!(decode(&EnvBlock::new()), "\0\0");
+
+        let mut block = EnvBlock::new();
+        block
+            .set("b", "2")
+            .and_then(|block| block.set("EMPTY", ""))
+            .and_then(|block| block.set("a", "1"))
+            .and_then(|block| block.set("Path", "C:\\old"))
+            .and_then(|block| block.set("PATH", "C:\\new"))
+            .expect("Failed to set variables");
+        assert_eq!(block.get("path"), Some(OsStr::new("C:\\new")));
+        assert_eq!(decode(&block), "a=1\0b=2\0EMPTY=\0PATH=C:\\new\0\0");
+
+        block.remove("B");
+        assert_eq!(block.get("b"), None);
+        assert_eq!(block.set("a\0", "1"), Err(UsvfsError::InteriorNul));
+        assert!(block.set("a=b", "1").is_err());
+        assert!(block.set("=C:", "C:\\").is_ok());
+    }
+
+    #[test]
+    fn connectByName() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("byNameTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+
+        let vfs = Vfs::connect_by_name("byNameTest").expect("Failed to connect by name");
+        let name = current_vfs_name();
+        drop(vfs);
+        drop(params);
+
+        assert!(name.is_some_and(|name| name.contains("byNameTest")));
+        assert!(matches!(
+            Vfs::connect_by_name(""),
+            Err(UsvfsError::EmptyInstanceName)
+        ));
+    }
+
+    #[test]
+    fn connectionState() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("stateTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        let connected = is_connected();
+        let name = connected_instance_name();
+        disconnect_vfs();
+
+        assert!(connected);
+        assert!(name.is_some_and(|name| name.contains("stateTest")));
+        assert!(!is_connected());
+        assert_eq!(connected_instance_name(), None);
+    }
+
+    #[test]
+    fn closeProcessInformation() {
+        let (_lock, _vfs) = test_vfs("closeTest");
+
+        let mut processInformation = PROCESS_INFORMATION::default();
+        create_process_hooked(
+            "C:\\Windows\\System32\\cmd.exe",
+            "cmd.exe /c exit",
+            CreateProcessOptions {
+                process_information: Some(&mut processInformation),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to spawn hooked process");
+
+        assert_eq!(close_process_information(&mut processInformation), Ok(()));
+        assert!(processInformation.hProcess.is_invalid());
+        assert_eq!(close_process_information(&mut processInformation), Ok(()));
+    }
+
+    #[test]
+    fn createRetry() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("retryTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        // zero attempts still tries once
+        let created = create_vfs_retry(&params, 0, time::Duration::from_millis(10));
+        disconnect_vfs();
+        assert_eq!(created, Ok(()));
+    }
+
+    #[test]
+    fn spawnSuspended() {
+        let (_lock, _vfs) = test_vfs("suspendedTest");
+
+        let process = spawn_suspended(
+            Path::new("C:\\Windows\\System32\\cmd.exe"),
+            "cmd.exe /c exit 5",
+        )
+        .expect("Failed to spawn suspended process");
+        assert_eq!(process.resume_main_thread(), Ok(()));
+        assert_eq!(process.wait(), Ok(5));
+    }
+
+    #[test]
+    fn staticStrMatchesUsvfs() {
+        for level in [
+            LogLevel::Debug,
+            LogLevel::Info,
+            LogLevel::Warning,
+            LogLevel::Error,
+        ] {
+            let upstream = unsafe { std::ffi::CStr::from_ptr(usvfsLogLevelToString(level)) };
+            assert_eq!(upstream.to_str(), Ok(level.as_static_str()));
+            assert_eq!(level.to_string(), level.as_static_str());
+        }
+        for dumpType in [
+            CrashDumpsType::Nil,
+            CrashDumpsType::Mini,
+            CrashDumpsType::Data,
+            CrashDumpsType::Full,
+        ] {
+            let upstream =
+                unsafe { std::ffi::CStr::from_ptr(usvfsCrashDumpTypeToString(dumpType)) };
+            assert_eq!(upstream.to_str(), Ok(dumpType.as_static_str()));
+            assert_eq!(dumpType.to_string(), dumpType.as_static_str());
+        }
+    }
+
+    #[cfg(feature = "delay-load")]
+    #[test]
+    fn loadMissingLibrary() {
+        let missing = Usvfs::load(&Path::new("C:\\usvfs-rs\\missing").join(Usvfs::LIBRARY_NAME));
+        assert!(matches!(missing, Err(UsvfsError::LoadFailed { .. })));
+        // ERROR_MOD_NOT_FOUND, taken from the error LoadLibraryW returned
+        assert_eq!(missing.unwrap_err().code(), Some(126));
+        assert!(Usvfs::load_default().is_ok());
+    }
+
+    #[cfg(feature = "delay-load")]
+    #[test]
+    fn loadWrongFileName() {
+        // the delay load helper would never find a DLL by another name
+        let renamed = std::env::current_dir()
+            .expect("Failed to get current directory")
+            .join("usvfs.dll");
+        let error = Usvfs::load(&renamed).expect_err("Loading a renamed DLL succeeded");
+        assert!(matches!(error, UsvfsError::LoadFailed { .. }));
+        assert_eq!(error.code(), Some(87));
+        assert!(Usvfs::load(Path::new(&Usvfs::LIBRARY_NAME.to_uppercase())).is_ok());
+    }
+
+    #[test]
+    fn linkWithBase() {
+        let dir = std::env::temp_dir().join("usvfs-rs-base");
+        std::fs::create_dir_all(dir.join("data")).expect("Failed to create test directory");
+        std::fs::write(dir.join("data").join("plugin.esp"), b"usvfs")
+            .expect("Failed to create test file");
+        let absolute = std::env::temp_dir().join("usvfs-rs-base-absolute.esp");
+        std::fs::write(&absolute, b"usvfs").expect("Failed to create test file");
+        let destination = std::env::temp_dir().join("usvfs-rs-base-destination");
+
+        let (_lock, mut vfs) = test_vfs("baseTest");
+        // a base that doesn't exist must not cost the session
+        let missing = vfs.with_base(dir.join("missing")).map(|_| ());
+        let connectedAfterMissing = is_connected();
+        vfs.with_base(dir.clone()).expect("Failed to set base");
+        let base = vfs.base().map(Path::to_path_buf);
+        let relative = vfs.link_file(
+            "data\\plugin.esp",
+            destination.join("plugin.esp"),
+            LinkFlags::empty(),
+        );
+        let linkedAbsolute = vfs.link_file(
+            &absolute,
+            destination.join("absolute.esp"),
+            LinkFlags::empty(),
+        );
+        let sources: Vec<PathBuf> = vfs
+            .mappings()
+            .into_iter()
+            .map(|mapping| mapping.source)
+            .collect();
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::remove_file(&absolute).ok();
+
+        let base = base.expect("base is not set");
+        assert!(matches!(missing, Err(UsvfsError::Io { .. })));
+        assert!(connectedAfterMissing);
+        assert!(base.is_absolute());
+        assert!(!base.to_string_lossy().starts_with(r"\\?\"));
+        assert_eq!(relative, Ok(()));
+        assert_eq!(linkedAbsolute, Ok(()));
+        assert_eq!(sources, [base.join("data\\plugin.esp"), absolute]);
+    }
+
+    #[test]
+    fn dryRunMatchesRealRun() {
+        let dir = std::env::temp_dir().join("usvfs-rs-dry-run");
+        std::fs::create_dir_all(dir.join("textures")).expect("Failed to create test directory");
+        std::fs::write(dir.join("plugin.esp"), b"usvfs").expect("Failed to create test file");
+        let longSource: PathBuf = ["a long directory name to pass MAX_PATH"; 8]
+            .iter()
+            .collect::<PathBuf>()
+            .join("long.esp");
+        std::fs::create_dir_all(dir.join(longSource.parent().expect("Failed to get parent")))
+            .expect("Failed to create test directory");
+        std::fs::write(dir.join(&longSource), b"usvfs").expect("Failed to create test file");
+        let destination = std::env::temp_dir().join("usvfs-rs-dry-run-destination");
+
+        let mut dryRun = DryRunVfs::new();
+        dryRun.with_base(dir.clone()).expect("Failed to set base");
+        dryRun
+            .link_file(
+                "plugin.esp",
+                destination.join("plugin.esp"),
+                LinkFlags::FAIL_IF_EXISTS,
+            )
+            .expect("Failed to record file link");
+        dryRun
+            .link_directory_static(
+                "textures",
+                destination.join("textures"),
+                LinkFlags::RECURSIVE,
+            )
+            .expect("Failed to record directory link");
+        // past MAX_PATH real links record the source with the \\?\ prefix
+        dryRun
+            .link_file(
+                &longSource,
+                destination.join("long.esp"),
+                LinkFlags::empty(),
+            )
+            .expect("Failed to record long file link");
+        let plan = dryRun.into_plan();
+
+        let (_lock, mut vfs) = test_vfs("dryRunTest");
+        vfs.with_base(dir.clone()).expect("Failed to set base");
+        let linkedFile = vfs.link_file(
+            "plugin.esp",
+            destination.join("plugin.esp"),
+            LinkFlags::FAIL_IF_EXISTS,
+        );
+        let linkedDirectory = vfs.link_directory_static(
+            "textures",
+            destination.join("textures"),
+            LinkFlags::RECURSIVE,
+        );
+        let linkedLong = vfs.link_file(
+            &longSource,
+            destination.join("long.esp"),
+            LinkFlags::empty(),
+        );
+        let mappings = vfs.mappings();
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(linkedFile, Ok(()));
+        assert_eq!(linkedDirectory, Ok(()));
+        assert_eq!(linkedLong, Ok(()));
+        assert_eq!(plan.len(), 3);
+        assert!(plan[2].source.to_string_lossy().starts_with("\\\\?\\"));
+        assert_eq!(plan, mappings);
+    }
+
+    #[test]
+    fn resetMappingsCount() {
+        let dir = std::env::temp_dir().join("usvfs-rs-reset");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+
+        let (_lock, mut vfs) = test_vfs("resetTest");
+        for name in ["a.esp", "b.esp", "c.esp"] {
+            let source = dir.join(name);
+            std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+            vfs.link_file(&source, dir.join("linked").join(name), LinkFlags::empty())
+                .expect("Failed to link file");
+        }
+        let cleared = vfs.reset_mappings();
+        let clearedAgain = vfs.reset_mappings();
+        let connected = is_connected();
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(cleared, 3);
+        assert_eq!(clearedAgain, 0);
+        assert!(connected);
+    }
+
+    #[test]
+    fn healthCheck() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("healthTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        let healthy = health_check();
+        disconnect_vfs();
+
+        assert_eq!(healthy, Ok(()));
+        assert_eq!(health_check(), Err(UsvfsError::NotConnected));
+    }
+
+    #[test]
+    fn errorConversions() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing.esp");
+        assert_eq!(
+            UsvfsError::from(io),
+            UsvfsError::Io {
+                kind: std::io::ErrorKind::NotFound,
+                message: "missing.esp".to_owned(),
+            }
+        );
+
+        let accessDenied = windows::core::Error::from(windows::core::HRESULT::from_win32(5));
+        assert_eq!(UsvfsError::from(accessDenied), UsvfsError::win32(5));
+        let notWin32 = windows::core::Error::from(windows::core::HRESULT(0x8000_4005u32 as i32));
+        assert_eq!(UsvfsError::from(notWin32), UsvfsError::win32(0x8000_4005));
+
+        assert_eq!(UsvfsError::win32(5).code(), Some(5));
+        let message = UsvfsError::win32(5).to_string();
+        assert!(message.ends_with("(win32 error 5)"));
+        assert!(message.len() > "(win32 error 5)".len());
+    }
+
+    #[test]
+    fn linkDirectoryFiltered() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-filtered");
+        let source = dir.join("source");
+        let destination = dir.join("destination");
+        std::fs::create_dir_all(source.join("textures")).expect("Failed to create test directory");
+        std::fs::create_dir_all(destination.join("textures"))
+            .expect("Failed to create test directory");
+        for name in [
+            "b.esp",
+            "a.txt",
+            "a.esp",
+            "textures\\sky.esp",
+            "textures\\sky.dds",
+        ] {
+            std::fs::write(source.join(name), b"usvfs").expect("Failed to create test file");
+        }
+
+        let params = ParametersHandle::builder()
+            .instance_name("filteredTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        let linked = virtually_link_directory_static_filtered(
+            &source,
+            &destination,
+            LinkFlags::RECURSIVE,
+            |path| path.extension().is_some_and(|extension| extension == "esp"),
+        );
+        let mappings = recorded_mappings();
+        let missing = virtually_link_directory_static_filtered(
+            dir.join("missing"),
+            &destination,
+            LinkFlags::empty(),
+            |_| true,
+        );
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(linked, Ok(()));
+        let linkedFiles: Vec<(PathBuf, PathBuf, MappingKind)> = mappings
+            .into_iter()
+            .map(|mapping| (mapping.source, mapping.destination, mapping.kind))
+            .collect();
+        assert_eq!(
+            linkedFiles,
+            ["a.esp", "b.esp", "textures\\sky.esp"].map(|name| (
+                source.join(name),
+                destination.join(name),
+                MappingKind::File
+            ))
+        );
+        assert!(matches!(
+            missing,
+            Err(UsvfsError::Io {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn logLevelGuard() {
+        let _lock = test_lock();
+        let mut params = ParametersHandle::builder()
+            .instance_name("logLevelTest")
+            .log_level(LogLevel::Warning)
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        assert_eq!(ParametersHandle::new().log_level(), LogLevel::Debug);
+        assert_eq!(params.duplicate().log_level(), LogLevel::Warning);
+        create_vfs(&params).expect("Failed to create VFS");
+
+        let previous = {
+            let guard = LogLevelGuard::raise(&mut params, LogLevel::Debug);
+            guard.previous()
+        };
+        let restored = params.log_level();
+        disconnect_vfs();
+
+        assert_eq!(previous, LogLevel::Warning);
+        assert_eq!(restored, LogLevel::Warning);
+    }
+
+    #[test]
+    fn parameterGetters() {
+        let mut params = ParametersHandle::new();
+        assert_eq!(params.instance_name(), "");
+        assert!(!params.debug_mode());
+        assert_eq!(params.crash_dumps_type(), CrashDumpsType::Nil);
+        assert_eq!(params.process_delay(), time::Duration::ZERO);
+
+        params
+            .set_instance_name("gettersTest")
+            .expect("Failed to set instance name");
+        params.set_debug_mode(true);
+        params.set_log_level(LogLevel::Error);
+        params.set_crash_dumps_type(CrashDumpsType::Mini);
+        params
+            .set_crash_dumps_path("C:\\dumps")
+            .expect("Failed to set crash dumps path");
+        params.set_process_delay(time::Duration::from_micros(1500));
+        let check = |params: &ParametersHandle| {
+            assert_eq!(params.instance_name(), "gettersTest");
+            assert!(params.debug_mode());
+            assert_eq!(params.log_level(), LogLevel::Error);
+            assert_eq!(params.crash_dumps_type(), CrashDumpsType::Mini);
+            assert_eq!(params.crash_dumps_path(), Path::new("C:\\dumps"));
+            assert_eq!(params.process_delay(), time::Duration::from_millis(1));
+        };
+        check(&params);
+        check(&params.duplicate());
+        let mut target = ParametersHandle::new();
+        params.copy_into(&mut target);
+        check(&target);
+
+        params.set_debug_mode(false);
+        assert!(!params.debug_mode());
+        params.set_process_delay(time::Duration::MAX);
+        assert_eq!(params.process_delay(), MAX_PROCESS_DELAY);
+    }
+
+    #[test]
+    fn disconnectIfConnected() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("disconnectTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+
+        assert!(disconnect_if_connected());
+        assert!(!disconnect_if_connected());
+        assert!(!is_connected());
+    }
+
+    #[test]
+    fn linkDirectoryProgress() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-progress");
+        let source = dir.join("source");
+        let destination = dir.join("destination");
+        std::fs::create_dir_all(source.join("meshes")).expect("Failed to create test directory");
+        std::fs::create_dir_all(destination.join("meshes"))
+            .expect("Failed to create test directory");
+        for name in ["a.esp", "b.esp", "meshes\\rock.nif"] {
+            std::fs::write(source.join(name), b"usvfs").expect("Failed to create test file");
+        }
+
+        let params = ParametersHandle::builder()
+            .instance_name("progressTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        let mut calls = Vec::new();
+        let linked = virtually_link_directory_static_progress(
+            &source,
+            &destination,
+            LinkFlags::empty(),
+            |done, total| calls.push((done, total)),
+        );
+        let mappingCount = recorded_mappings().len();
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(linked, Ok(()));
+        assert_eq!(calls, [(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(mappingCount, 3);
+    }
+
+    #[test]
+    fn linkDirectoryListed() {
+        let _lock = test_lock();
+        let dir = std::env::temp_dir().join("usvfs-rs-listed");
+        let source = dir.join("source");
+        let destination = dir.join("destination");
+        std::fs::create_dir_all(source.join(".Git")).expect("Failed to create test directory");
+        std::fs::create_dir_all(&destination).expect("Failed to create test directory");
+        for name in ["plugin.esp", "README.TXT", ".Git\\config"] {
+            std::fs::write(source.join(name), b"usvfs").expect("Failed to create test file");
+        }
+
+        let params = ParametersHandle::builder()
+            .instance_name("listedTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        add_skip_file_suffix(".txt");
+        add_skip_directory(".git");
+        let linked =
+            virtually_link_directory_static_listed(&source, &destination, LinkFlags::RECURSIVE);
+        let mappingCount = recorded_mappings().len();
+        clear_skip_file_suffixes();
+        clear_skip_directories();
+        disconnect_vfs();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(linked, Ok(vec![destination.join("plugin.esp")]));
+        assert_eq!(mappingCount, 1);
+    }
+
+    #[test]
+    fn latestCrashDump() {
+        let dir = std::env::temp_dir().join("usvfs-rs-crash-dumps");
+        std::fs::create_dir_all(dir.join("nested.dmp")).expect("Failed to create test directory");
+        let now = time::SystemTime::now();
+        for (name, age) in [("old.dmp", 60), ("new.DMP", 10), ("newer.txt", 0)] {
+            let file = File::create(dir.join(name)).expect("Failed to create test file");
+            file.set_modified(now - time::Duration::from_secs(age))
+                .expect("Failed to set modification time");
+        }
+
+        let latest = latest_crash_dump(&dir);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(latest, Some(dir.join("new.DMP")));
+        assert_eq!(latest_crash_dump(&dir), None);
+    }
+
+    #[test]
+    fn vfsNameTruncation() {
+        let _lock = test_lock();
+        let params = ParametersHandle::builder()
+            .instance_name("truncatedNameTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        let mut small = [0xFFu8; 4];
+        let needed = get_current_VFS_name(&mut small);
+        let mut exact = vec![0xFFu8; needed];
+        let neededExact = get_current_VFS_name(&mut exact);
+        let neededEmpty = get_current_VFS_name(&mut []);
+        let name = current_vfs_name();
+        disconnect_vfs();
+
+        let name = name.expect("No VFS name");
+        assert_eq!(needed, name.len() + 1);
+        assert!(needed > small.len());
+        assert_eq!(&small[..3], &name.as_bytes()[..3]);
+        assert_eq!(small[3], 0x00);
+        assert_eq!(neededExact, needed);
+        assert_eq!(narrow_to_string(&exact), name);
+        assert_eq!(neededEmpty, needed);
+    }
+
+    #[test]
+    fn resolveVirtualCached() {
+        let dir = std::env::temp_dir().join("usvfs-rs-resolve-virtual");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let first = dir.join("first.esp");
+        let second = dir.join("second.esp");
+        for source in [&first, &second] {
+            std::fs::write(source, b"usvfs").expect("Failed to create test file");
+        }
+        let lowercase =
+            |path: Option<PathBuf>| path.map(|path| path.to_string_lossy().to_lowercase());
+
+        let (_lock, mut vfs) = test_vfs("resolveVirtualTest");
+        vfs.link_file(&first, dir.join("linked_first.esp"), LinkFlags::empty())
+            .expect("Failed to link file");
+        let resolvedFirst = vfs.resolve_virtual(&dir.join("linked_first.esp"));
+        // linking again has to drop the cached tree
+        vfs.link_file(&second, dir.join("linked_second.esp"), LinkFlags::empty())
+            .expect("Failed to link file");
+        let resolvedSecond = vfs.resolve_virtual(&dir.join("linked_second.esp"));
+        vfs.clear_mappings();
+        let resolvedCleared = vfs.resolve_virtual(&dir.join("linked_first.esp"));
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(lowercase(resolvedFirst), lowercase(Some(first)));
+        assert_eq!(lowercase(resolvedSecond), lowercase(Some(second)));
+        assert_eq!(resolvedCleared, None);
+    }
+
+    #[test]
+    fn linkBytes() {
+        let dir = std::env::temp_dir().join("usvfs-rs-link-bytes");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let destination = dir.join("patched.ini");
+
+        let (_lock, mut vfs) = test_vfs("linkBytesTest");
+        let file = vfs
+            .link_bytes(b"[General]\nbPatched=1\n", &destination, LinkFlags::empty())
+            .expect("Failed to link bytes");
+        let contents = std::fs::read(file.path());
+        let mappings = vfs.mappings();
+        let path = file.path().to_path_buf();
+        drop(file);
+        drop(vfs);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(
+            contents.expect("Failed to read temp file"),
+            b"[General]\nbPatched=1\n"
+        );
+        assert!(path.to_string_lossy().ends_with("-patched.ini"));
+        assert_eq!(mappings.last().map(|mapping| &mapping.source), Some(&path));
+        assert_eq!(
+            mappings.last().map(|mapping| &mapping.destination),
+            Some(&destination)
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn runInVfsErrors() {
+        let _lock = test_lock();
+        let command = HookedCommand::new("C:\\Windows\\System32\\cmd.exe");
+        assert_eq!(
+            run_in_vfs(VfsConfig::default(), &[], command.clone()),
+            Err(UsvfsError::EmptyInstanceName)
+        );
+
+        let dir = std::env::temp_dir().join("usvfs-rs-run-in-vfs");
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        let source = dir.join("source.esp");
+        std::fs::write(&source, b"usvfs").expect("Failed to create test file");
+        let config = VfsConfig {
+            instance_name: "runInVfsTest".to_owned(),
+            ..VfsConfig::default()
+        };
+        // the destination directory doesn't exist, not even virtually
+        let mappings = [MappingRecord {
+            source,
+            destination: dir.join("missing").join("nested").join("broken.esp"),
+            flags: LinkFlags::empty(),
+            kind: MappingKind::File,
+        }];
+        let failed = run_in_vfs(config, &mappings, command);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(matches!(failed, Err(UsvfsError::LinkFailed { .. })));
+    }
+
+    #[test]
+    fn linkedCapabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, usvfs_version());
+        assert!(!capabilities.version.is_empty());
+        let numbers = capabilities
+            .version_numbers
+            .expect("Failed to parse usvfs version");
+        assert!(!numbers.is_empty());
+        assert!(!capabilities.supports_dynamic_directory);
+        assert!(!capabilities.supports_cow);
+    }
+
+    #[test]
+    fn operationTimeout() {
+        let _lock = test_lock();
+        let slow = with_timeout(time::Duration::from_millis(20), || {
+            std::thread::sleep(time::Duration::from_millis(500));
+            Ok(())
+        });
+        assert_eq!(slow, Err(UsvfsError::TimedOut));
+
+        let fast = with_timeout(time::Duration::from_secs(5), || Ok(4));
+        assert_eq!(fast, Ok(4));
+        let failed: Result<(), _> = with_timeout(time::Duration::from_secs(5), || {
+            Err(UsvfsError::InteriorNul)
+        });
+        assert_eq!(failed, Err(UsvfsError::InteriorNul));
 
-        init_logging(false);
-        create_vfs(testParams).expect("Failed to create VFS");
+        let params = ParametersHandle::builder()
+            .instance_name("timeoutTest")
+            .crash_dumps_type(CrashDumpsType::Nil)
+            .build()
+            .expect("Failed to build parameters");
+        create_vfs(&params).expect("Failed to create VFS");
+        let dump = vfs_dump_timeout(time::Duration::from_secs(5));
         disconnect_vfs();
-        testParams.free_parameters();
+        assert!(dump.is_ok());
     }
 }
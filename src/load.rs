@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{ERROR_INVALID_PARAMETER, HMODULE},
+        System::LibraryLoader::LoadLibraryW,
+    },
+};
+
+use crate::{error::error_code, widen_path, UsvfsError};
+
+/// Proof that the usvfs DLL is loaded into the process.
+///
+/// With the delay-load feature the DLL isn't loaded when the process starts
+/// but on the first call into it, and a missing DLL makes that call crash the
+/// process. Loading it through Usvfs before calling anything else turns that
+/// into an error that can be reported. Once loaded, the delay load helper
+/// finds the DLL by its name and every function of this crate uses it, so the
+/// DLL stays loaded for the rest of the process.
+///
+/// The linker arguments cargo takes from this crate's build script only apply
+/// to this crate's own tests and examples, not to the binary depending on it.
+/// So the delay-load feature alone leaves the DLL a regular import of an
+/// application, which then still fails to start without it. The application
+/// has to ask for delay loading in its own build script:
+///
+/// ```text
+/// fn main() {
+///     println!("cargo:rustc-link-arg=/DELAYLOAD:usvfs_x64.dll");
+///     println!("cargo:rustc-link-lib=delayimp");
+/// }
+/// ```
+///
+/// with usvfs_x86.dll for 32 bit targets, see LIBRARY_NAME
+#[derive(Debug)]
+pub struct Usvfs {
+    _module: HMODULE,
+}
+
+impl Usvfs {
+    /// the file name of the usvfs DLL for the target architecture
+    #[cfg(target_arch = "x86_64")]
+    pub const LIBRARY_NAME: &'static str = "usvfs_x64.dll";
+    /// the file name of the usvfs DLL for the target architecture
+    #[cfg(target_arch = "x86")]
+    pub const LIBRARY_NAME: &'static str = "usvfs_x86.dll";
+
+    /// load the usvfs DLL at path. The file has to be named LIBRARY_NAME,
+    /// since that's the name the delay load helper looks for, any other name
+    /// fails with LoadFailed and ERROR_INVALID_PARAMETER without loading it
+    pub fn load(path: &Path) -> Result<Self, UsvfsError> {
+        let named_right = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.eq_ignore_ascii_case(Self::LIBRARY_NAME));
+        if !named_right {
+            return Err(UsvfsError::LoadFailed {
+                path: path.display().to_string(),
+                code: ERROR_INVALID_PARAMETER.0,
+            });
+        }
+        let wide_path = widen_path(path);
+        match unsafe { LoadLibraryW(PCWSTR(wide_path.as_ptr())) } {
+            Ok(module) => Ok(Self { _module: module }),
+            // the error already carries the code LoadLibraryW failed with
+            Err(error) => Err(UsvfsError::LoadFailed {
+                path: path.display().to_string(),
+                code: error_code(&error),
+            }),
+        }
+    }
+
+    /// load the usvfs DLL from the standard DLL search path, like the
+    /// delay load helper would
+    pub fn load_default() -> Result<Self, UsvfsError> {
+        Self::load(Path::new(Self::LIBRARY_NAME))
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{narrow_to_string, usvfsGetLogMessages, LogLevel, LOG_MESSAGE_BUFFER_SIZE};
+
+/// One message from the usvfs log, split into its parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMessage {
+    /// the level the message was logged at
+    pub level: LogLevel,
+    /// the message body without the line prefix
+    pub text: String,
+    /// the local time of day the message was logged at, since midnight,
+    /// None if the line has no time in the usvfs format
+    pub time_of_day: Option<Duration>,
+    /// when the message was logged, if known. Only filled in with the
+    /// chrono feature, see LogMessage::parse()
+    pub timestamp: Option<SystemTime>,
+    /// whether the line filled a whole slot of the log queue, which means
+    /// usvfs cut off the end when the message was logged
+    pub truncated: bool,
+}
+
+impl LogMessage {
+    /// splits a line of the usvfs log into a message. usvfs formats its
+    /// log lines with the spdlog pattern `%H:%M:%S.%e <%P:%t> [%L] %v`,
+    /// e.g. `12:34:56.789 <1234:5678> [W] some text`, where `%L` is the
+    /// first letter of the level. The body is everything after the level,
+    /// lines that don't match the pattern are kept whole as the text and
+    /// get the Info level.
+    ///
+    /// The time in the prefix is the local time of day with milliseconds,
+    /// from a 24 hour clock, and has no date. It is kept as time_of_day,
+    /// which sorts messages of one day. With the chrono feature timestamp
+    /// adds the date the message was most likely logged at: today, or
+    /// yesterday if the time is later than now, e.g. for a message logged
+    /// before midnight and read after. Without the feature, or if the time
+    /// doesn't parse, timestamp is None
+    pub fn parse(line: &str) -> Self {
+        // the terminator takes the last byte of a slot
+        let truncated = line.len() >= LOG_MESSAGE_BUFFER_SIZE - 1;
+        let line = line.trim_end_matches(['\r', '\n']);
+        match split_line(line) {
+            Some((time, level, text)) => {
+                let time_of_day = parse_time_of_day(time);
+                Self {
+                    level,
+                    text: text.to_string(),
+                    time_of_day,
+                    timestamp: time_of_day.and_then(timestamp),
+                    truncated,
+                }
+            }
+            None => Self {
+                level: LogLevel::Info,
+                text: line.to_string(),
+                time_of_day: None,
+                timestamp: None,
+                truncated,
+            },
+        }
+    }
+}
+
+/// split a log line into its time, level and body, None if it doesn't have
+/// the usvfs prefix
+fn split_line(line: &str) -> Option<(&str, LogLevel, &str)> {
+    let (time, rest) = line.split_once(' ')?;
+    let (process, rest) = rest.split_once(' ')?;
+    if !(process.starts_with('<') && process.ends_with('>')) {
+        return None;
+    }
+    let rest = rest.strip_prefix('[')?;
+    let (level, text) = rest
+        .split_once("] ")
+        .or_else(|| Some((rest.strip_suffix(']')?, "")))?;
+    let level = match level {
+        "T" | "D" => LogLevel::Debug,
+        "I" => LogLevel::Info,
+        "W" => LogLevel::Warning,
+        "E" | "C" => LogLevel::Error,
+        _ => return None,
+    };
+    Some((time, level, text))
+}
+
+/// the time since midnight of a `%H:%M:%S.%e` time like `12:34:56.789`
+fn parse_time_of_day(time: &str) -> Option<Duration> {
+    // a field of exactly digits digits, below limit
+    let number = |field: &str, digits: usize, limit: u64| {
+        let all_digits = field.len() == digits && field.bytes().all(|byte| byte.is_ascii_digit());
+        all_digits
+            .then(|| field.parse::<u64>().ok())
+            .flatten()
+            .filter(|value| *value < limit)
+    };
+    let (clock, millis) = time.split_once('.')?;
+    let [hours, minutes, seconds]: [&str; 3] =
+        clock.split(':').collect::<Vec<_>>().try_into().ok()?;
+    let seconds =
+        (number(hours, 2, 24)? * 60 + number(minutes, 2, 60)?) * 60 + number(seconds, 2, 60)?;
+    Some(Duration::from_millis(
+        seconds * 1000 + number(millis, 3, 1000)?,
+    ))
+}
+
+/// the moment the local time_of_day was last reached, see LogMessage::parse()
+#[cfg(feature = "chrono")]
+fn timestamp(time_of_day: Duration) -> Option<SystemTime> {
+    use chrono::{Local, NaiveTime, TimeZone};
+
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(
+        time_of_day.as_secs() as u32,
+        time_of_day.subsec_nanos(),
+    )?;
+    let now = Local::now().naive_local();
+    let mut logged = now.date().and_time(time);
+    if logged > now {
+        logged = logged.date().pred_opt()?.and_time(time);
+    }
+    Local
+        .from_local_datetime(&logged)
+        .earliest()
+        .map(SystemTime::from)
+}
+
+/// without chrono the date, and with it the timestamp, is unknown
+#[cfg(not(feature = "chrono"))]
+fn timestamp(_time_of_day: Duration) -> Option<SystemTime> {
+    None
+}
+
+/// get the next queued log message without blocking, None if the queue is empty
+pub fn next_log_message() -> Option<LogMessage> {
+    let mut buffer = vec![0u8; LOG_MESSAGE_BUFFER_SIZE];
+    unsafe {
+        if !usvfsGetLogMessages(buffer.as_mut_ptr(), buffer.len(), false) {
+            return None;
+        }
+    }
+    Some(LogMessage::parse(&narrow_to_string(&buffer)))
+}
+
+/// how long next_log_message_timeout() sleeps between looking at the queue
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// get the next queued log message, waiting up to timeout for one to be
+/// logged. None if the queue stayed empty that long.
+///
+/// usvfsGetLogMessages has a blocking mode, but upstream never implemented
+/// it, so this polls the queue every LOG_POLL_INTERVAL instead. A message
+/// is therefore picked up up to that much later than it was logged
+pub fn next_log_message_timeout(timeout: Duration) -> Option<LogMessage> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(message) = next_log_message() {
+            return Some(message);
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+        thread::sleep(remaining.min(LOG_POLL_INTERVAL));
+    }
+}
+
+/// how long the log pump sleeps after finding the queue empty
+const LOG_PUMP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// spawn a thread that forwards every log message to the returned
+/// receiver as it arrives. The thread stops once stop is set, or when
+/// it tries to send after the receiver was dropped.
+///
+/// The pump reads from the VFS the process is connected to, so set stop
+/// and join the thread before disconnecting rather than letting it
+/// outlive the connection
+pub fn spawn_log_pump(stop: Arc<AtomicBool>) -> (JoinHandle<()>, Receiver<LogMessage>) {
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            match next_log_message() {
+                Some(message) => {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+                None => thread::sleep(LOG_PUMP_INTERVAL),
+            }
+        }
+    });
+    (handle, receiver)
+}
+
+/// emit every queued log message as a tracing event with the usvfs target,
+/// at the level matching its LogLevel. pid is attached as a field when given
+/// so events can be told apart per hooked process. Like drain_log_messages()
+/// this returns once the queue is empty, so call it periodically
+#[cfg(feature = "tracing")]
+pub fn pump_into_tracing(process_pid: Option<u32>) {
+    while let Some(message) = next_log_message() {
+        let text = message.text;
+        match message.level {
+            LogLevel::Debug => tracing::debug!(target: "usvfs", pid = process_pid, "{text}"),
+            LogLevel::Info => tracing::info!(target: "usvfs", pid = process_pid, "{text}"),
+            LogLevel::Warning => tracing::warn!(target: "usvfs", pid = process_pid, "{text}"),
+            LogLevel::Error => tracing::error!(target: "usvfs", pid = process_pid, "{text}"),
+        }
+    }
+}
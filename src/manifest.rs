@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use crate::{virtually_link_directory_static, virtually_link_file, LinkFlags, UsvfsError};
+
+/// One link read from a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub flags: LinkFlags,
+}
+
+/// parse a mapping manifest without applying it.
+///
+/// A manifest has one link per line, made of the source, the destination
+/// and optionally the flags, separated by tabs since Windows paths can't
+/// contain them:
+///
+/// ```text
+/// # lines starting with # and empty lines are ignored
+/// C:\mods\a\plugin.esp\tC:\game\data\plugin.esp
+/// C:\mods\b\textures\tC:\game\data\textures\tRECURSIVE | FAIL_IF_EXISTS
+/// ```
+///
+/// where each `\t` stands for a tab
+///
+/// Flags are the names of LinkFlags constants joined by |. A malformed
+/// line fails the whole manifest with the 1-based line number
+pub fn parse_manifest(manifest: &str) -> Result<Vec<ManifestEntry>, UsvfsError> {
+    let mut entries = Vec::new();
+    for (index, line) in manifest.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: String| UsvfsError::ManifestInvalid {
+            line: index + 1,
+            reason,
+        };
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (source, destination, flags) = match fields.as_slice() {
+            [source, destination] => (source, destination, LinkFlags::empty()),
+            [source, destination, flags] => {
+                (source, destination, parse_flags(flags).map_err(invalid)?)
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "expected 2 or 3 tab separated fields, found {}",
+                    fields.len()
+                )))
+            }
+        };
+        if source.is_empty() || destination.is_empty() {
+            return Err(invalid(
+                "source and destination must not be empty".to_owned(),
+            ));
+        }
+
+        entries.push(ManifestEntry {
+            source: PathBuf::from(source),
+            destination: PathBuf::from(destination),
+            flags,
+        });
+    }
+    Ok(entries)
+}
+
+/// parse "NAME | NAME" into LinkFlags
+fn parse_flags(flags: &str) -> Result<LinkFlags, String> {
+    let mut parsed = LinkFlags::empty();
+    for name in flags
+        .split('|')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let (_, flag) = LinkFlags::all()
+            .iter_names()
+            .find(|(flag_name, _)| *flag_name == name)
+            .ok_or_else(|| format!("unknown link flag \"{name}\""))?;
+        parsed |= flag;
+    }
+    Ok(parsed)
+}
+
+/// read a mapping manifest from path, see parse_manifest() for the format,
+/// and link every entry. Sources that are directories are linked with
+/// virtually_link_directory_static(), everything else with virtually_link_file().
+///
+/// The whole manifest is validated before anything is linked, so a malformed
+/// line leaves the VFS untouched. A link that fails while applying stops at
+/// that entry, the entries before it stay linked
+pub fn apply_manifest(path: &Path) -> Result<(), UsvfsError> {
+    let manifest = std::fs::read_to_string(path).map_err(|error| UsvfsError::Io {
+        kind: error.kind(),
+        message: format!("{}: {error}", path.display()),
+    })?;
+
+    for entry in parse_manifest(&manifest)? {
+        match entry.source.is_dir() {
+            true => {
+                virtually_link_directory_static(&entry.source, &entry.destination, entry.flags)?
+            }
+            false => virtually_link_file(&entry.source, &entry.destination, entry.flags)?,
+        }
+    }
+    Ok(())
+}
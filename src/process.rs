@@ -0,0 +1,290 @@
+use std::{
+    ffi::OsString,
+    os::windows::ffi::OsStringExt,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows::{
+    core::PWSTR,
+    Win32::{
+        Foundation::{
+            CloseHandle, BOOL, ERROR_INVALID_PARAMETER, HANDLE, MAX_PATH, WAIT_FAILED,
+            WAIT_TIMEOUT, WIN32_ERROR,
+        },
+        System::Threading::{
+            GetExitCodeProcess, OpenProcess, QueryFullProcessImageNameW, ResumeThread,
+            WaitForSingleObject, INFINITE, PROCESS_INFORMATION, PROCESS_NAME_WIN32,
+            PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
+        },
+    },
+};
+
+use crate::{connected_instance_name, error::last_error, vfs_process_list, UsvfsError};
+
+/// A process spawned into the VFS. Owns the process and main thread
+/// handles CreateProcess returned and closes them when dropped
+#[derive(Debug)]
+pub struct HookedProcess {
+    process: HANDLE,
+    thread: HANDLE,
+    pid: u32,
+    thread_id: u32,
+}
+
+impl HookedProcess {
+    /// Takes ownership of the handles in a PROCESS_INFORMATION
+    ///
+    /// # Safety
+    /// The handles must be valid and must not be closed by anything
+    /// else, the HookedProcess closes them when dropped
+    pub unsafe fn from_raw(process_information: PROCESS_INFORMATION) -> Self {
+        Self {
+            process: process_information.hProcess,
+            thread: process_information.hThread,
+            pid: process_information.dwProcessId,
+            thread_id: process_information.dwThreadId,
+        }
+    }
+
+    /// the process id
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// the id of the process's main thread
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// the raw process handle, only valid while self is alive
+    pub fn process_handle(&self) -> HANDLE {
+        self.process
+    }
+
+    /// the raw main thread handle, only valid while self is alive
+    pub fn thread_handle(&self) -> HANDLE {
+        self.thread
+    }
+
+    /// resume the main thread of a process created with CREATE_SUSPENDED, see
+    /// spawn_suspended(). The thread handle stays open until self is dropped,
+    /// so this works at any point before that. Resuming a thread that isn't
+    /// suspended does nothing
+    pub fn resume_main_thread(&self) -> Result<(), UsvfsError> {
+        match unsafe { ResumeThread(self.thread) } {
+            u32::MAX => Err(UsvfsError::ResumeFailed {
+                pid: self.pid,
+                code: last_error(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// block until the process exits and return its exit code
+    pub fn wait(&self) -> Result<u32, UsvfsError> {
+        let mut exit_code = 0u32;
+        unsafe {
+            if WaitForSingleObject(self.process, INFINITE) == WAIT_FAILED {
+                return Err(UsvfsError::WaitFailed {
+                    pid: self.pid,
+                    code: last_error(),
+                });
+            }
+            GetExitCodeProcess(self.process, &mut exit_code).map_err(|_| {
+                UsvfsError::WaitFailed {
+                    pid: self.pid,
+                    code: last_error(),
+                }
+            })?;
+        }
+        Ok(exit_code)
+    }
+}
+
+/// close the process and thread handles of a PROCESS_INFORMATION filled in by
+/// create_process_hooked(), which leaves them open for the caller. Call this
+/// once the handles aren't needed anymore, or use HookedProcess::from_raw()
+/// to have them closed on drop instead. The handles are reset afterwards, so
+/// closing the same PROCESS_INFORMATION twice does nothing the second time
+pub fn close_process_information(
+    process_information: &mut PROCESS_INFORMATION,
+) -> Result<(), UsvfsError> {
+    let mut result = Ok(());
+    for handle in [
+        &mut process_information.hThread,
+        &mut process_information.hProcess,
+    ] {
+        if handle.is_invalid() {
+            continue;
+        }
+        if unsafe { CloseHandle(*handle) }.is_err() {
+            result = result.and(Err(UsvfsError::CloseFailed { code: last_error() }));
+        }
+        *handle = HANDLE::default();
+    }
+    result
+}
+
+impl Drop for HookedProcess {
+    fn drop(&mut self) {
+        unsafe {
+            _ = CloseHandle(self.thread);
+            _ = CloseHandle(self.process);
+        }
+    }
+}
+
+/// how long wait_for_all_processes() waits before listing the hooked
+/// processes again
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// block until no process is hooked into the VFS anymore, or until
+/// timeout elapses. Processes that start while waiting, e.g. children
+/// of a hooked process, are waited for too since the list is polled
+/// again after every round of waits. The calling process is never
+/// waited for.
+///
+/// usvfs keeps listing a process that was killed before it could
+/// unregister, and one that can't be opened, e.g. since it runs elevated,
+/// can't be waited for. Listed pids that fail to open are therefore taken
+/// as exited and left out from then on
+pub fn wait_for_all_processes(timeout: Option<Duration>) -> Result<(), UsvfsError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let own_pid = std::process::id();
+    let mut gone = Vec::new();
+
+    loop {
+        let pids: Vec<u32> = vfs_process_list()?
+            .into_iter()
+            .filter(|pid| *pid != own_pid && !gone.contains(pid))
+            .collect();
+        if pids.is_empty() {
+            return Ok(());
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(UsvfsError::TimedOut);
+        }
+
+        for pid in pids {
+            let wait_millis = wait_millis(
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+            );
+
+            // the process may have exited between listing and opening it,
+            // which is just as good as waiting for it
+            let Ok(process) = (unsafe { OpenProcess(PROCESS_SYNCHRONIZE, BOOL::from(false), pid) })
+            else {
+                gone.push(pid);
+                continue;
+            };
+            let result = unsafe { WaitForSingleObject(process, wait_millis) };
+            let code = last_error();
+            unsafe {
+                _ = CloseHandle(process);
+            }
+
+            if result == WAIT_FAILED {
+                return Err(UsvfsError::WaitFailed { pid, code });
+            }
+            if result == WAIT_TIMEOUT {
+                return Err(UsvfsError::TimedOut);
+            }
+        }
+
+        // exited processes may take a moment to leave the list
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        thread::sleep(remaining.map_or(PROCESS_POLL_INTERVAL, |remaining| {
+            remaining.min(PROCESS_POLL_INTERVAL)
+        }));
+    }
+}
+
+/// block until the process pid exits, or until timeout elapses, and return
+/// its exit code, or None if it was still running at the timeout. Unlike
+/// wait_for_all_processes() this works for any process the caller may
+/// open, hooked or not. A pid that no process has, e.g. since it exited
+/// and nothing holds a handle to it anymore, fails with NoSuchProcess
+pub fn wait_for_pid(pid: u32, timeout: Option<Duration>) -> Result<Option<u32>, UsvfsError> {
+    let access = PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION;
+    let process = unsafe { OpenProcess(access, BOOL::from(false), pid) }.map_err(|_| {
+        match WIN32_ERROR(last_error()) {
+            ERROR_INVALID_PARAMETER => UsvfsError::NoSuchProcess { pid },
+            code => UsvfsError::WaitFailed { pid, code: code.0 },
+        }
+    })?;
+
+    let result = unsafe { WaitForSingleObject(process, wait_millis(timeout)) };
+    let mut exit_code = 0u32;
+    let exited = match result {
+        WAIT_TIMEOUT => Ok(None),
+        WAIT_FAILED => Err(last_error()),
+        _ => unsafe { GetExitCodeProcess(process, &mut exit_code) }
+            .map(|_| Some(exit_code))
+            .map_err(|_| last_error()),
+    };
+    unsafe {
+        _ = CloseHandle(process);
+    }
+    exited.map_err(|code| UsvfsError::WaitFailed { pid, code })
+}
+
+/// the milliseconds to wait for the remaining time, INFINITE for None.
+/// INFINITE is u32::MAX, so a long timeout stays one below to stay finite
+fn wait_millis(remaining: Option<Duration>) -> u32 {
+    match remaining {
+        Some(remaining) => remaining.as_millis().min(u128::from(INFINITE - 1)) as u32,
+        None => INFINITE,
+    }
+}
+
+/// A process hooked into the VFS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// the instance name of the VFS the process is attached to. usvfs only
+    /// lists the processes of the VFS the caller is connected to, so this
+    /// is always connected_instance_name()
+    pub instance: Option<String>,
+    /// the full path of the executable, None if the process exited or
+    /// can't be opened
+    pub image_path: Option<PathBuf>,
+}
+
+/// the processes hooked into the VFS, like vfs_process_list() but paired
+/// with the executable each one runs. usvfs reports nothing but the pids,
+/// the rest is looked up here
+pub fn vfs_process_infos() -> Result<Vec<ProcessInfo>, UsvfsError> {
+    let instance = connected_instance_name();
+    Ok(vfs_process_list()?
+        .into_iter()
+        .map(|pid| ProcessInfo {
+            pid,
+            instance: instance.clone(),
+            image_path: image_path(pid),
+        })
+        .collect())
+}
+
+/// the full path of the executable pid runs
+fn image_path(pid: u32) -> Option<PathBuf> {
+    unsafe {
+        let process =
+            OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, BOOL::from(false), pid).ok()?;
+        // long path aware executables can live past MAX_PATH
+        let mut buffer = vec![0u16; 32 * MAX_PATH as usize];
+        let mut length = buffer.len() as u32;
+        let queried = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut length,
+        );
+        _ = CloseHandle(process);
+        queried.ok()?;
+        Some(PathBuf::from(OsString::from_wide(
+            &buffer[..length as usize],
+        )))
+    }
+}
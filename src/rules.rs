@@ -0,0 +1,70 @@
+use crate::{
+    add_skip_directory, add_skip_file_suffix, blacklist_executable, blacklisted_executables,
+    clear_executable_blacklist, clear_library_force_loads, clear_skip_directories,
+    clear_skip_file_suffixes, force_load_library, forced_libraries, skip_file_suffixes,
+    skipped_directories,
+};
+
+/// Every global rule usvfs applies to linking and hooking, collected in one
+/// place so the whole set can be built up in Rust and applied in one call.
+///
+/// usvfs can't report the rules it currently holds, so there is no way to
+/// read the active set back from the VFS. capture() reads the rules this
+/// crate has applied instead, which is the whole set as long as nothing
+/// else adds rules to the same VFS.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlobalRules {
+    /// file suffixes skipped during linking, see add_skip_file_suffix()
+    pub skip_file_suffixes: Vec<String>,
+    /// directory names skipped during linking, see add_skip_directory()
+    pub skip_directories: Vec<String>,
+    /// executables that aren't hooked, see blacklist_executable()
+    pub blacklist: Vec<String>,
+    /// (process name, library path) pairs, see force_load_library()
+    pub force_loads: Vec<(String, String)>,
+}
+
+impl GlobalRules {
+    /// replace all four rule lists of the VFS with these. Each list is
+    /// cleared and then filled again, usvfs has no way to swap them in a
+    /// single step, so a process hooked while this runs may see some of
+    /// the lists empty
+    pub fn apply(&self) {
+        clear_skip_file_suffixes();
+        clear_skip_directories();
+        clear_executable_blacklist();
+        clear_library_force_loads();
+
+        for suffix in &self.skip_file_suffixes {
+            add_skip_file_suffix(suffix);
+        }
+        for directory in &self.skip_directories {
+            add_skip_directory(directory);
+        }
+        for name in &self.blacklist {
+            blacklist_executable(name);
+        }
+        for (process_name, library_path) in &self.force_loads {
+            force_load_library(process_name, library_path);
+        }
+    }
+
+    /// the rules applied through this crate since each list was last
+    /// cleared, e.g. to put them back with restore() after changing them
+    /// for a scoped operation. The lists are read one after the other, so
+    /// rules changed on another thread meanwhile may be caught half way
+    pub fn capture() -> Self {
+        Self {
+            skip_file_suffixes: skip_file_suffixes(),
+            skip_directories: skipped_directories(),
+            blacklist: blacklisted_executables(),
+            force_loads: forced_libraries(),
+        }
+    }
+
+    /// put back the rules of a snapshot taken with capture(), clearing
+    /// whatever was added since. Same as snapshot.apply()
+    pub fn restore(snapshot: &Self) {
+        snapshot.apply();
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{virtually_link_file, LinkFlags, UsvfsError};
+
+/// how many temp files link_bytes() created in this process, keeps their names apart
+static TEMP_FILES: AtomicU32 = AtomicU32::new(0);
+
+/// A temp file linked into the VFS by link_bytes(), removed when dropped.
+///
+/// Dropping it doesn't remove the link, usvfs can't remove single mappings,
+/// so the destination keeps pointing at the file after it is gone and
+/// processes opening it get file not found. Keep the guard alive for as long
+/// as any hooked process may read the file, and only drop it once they have
+/// exited or the mappings were cleared
+#[derive(Debug)]
+pub struct LinkedTempFile {
+    path: PathBuf,
+}
+
+impl LinkedTempFile {
+    /// the real path of the temp file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LinkedTempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// write contents to a new file in the temp directory and link it to
+/// destination, e.g. to show generated content like a patched config at a
+/// virtual path. The temp file has the same name as destination behind a
+/// unique prefix. It is removed again when the returned guard is dropped,
+/// or right away if linking fails
+pub fn link_bytes(
+    contents: &[u8],
+    destination: &Path,
+    flags: LinkFlags,
+) -> Result<LinkedTempFile, UsvfsError> {
+    let name = destination
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = std::env::temp_dir().join(format!(
+        "usvfs-rs-{}-{}-{name}",
+        std::process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, contents).map_err(|error| UsvfsError::Io {
+        kind: error.kind(),
+        message: format!("{}: {error}", path.display()),
+    })?;
+
+    // from here on the guard cleans up, also when linking fails
+    let file = LinkedTempFile { path };
+    virtually_link_file(&file.path, destination, flags)?;
+    Ok(file)
+}
@@ -0,0 +1,59 @@
+use std::{path::PathBuf, sync::mpsc, thread, time::Duration};
+
+use crate::{
+    vfs_dump, virtually_link_directory_static, virtually_link_file, LinkFlags, UsvfsError,
+};
+
+/// run operation on a worker thread and wait at most timeout for it,
+/// failing with TimedOut if it takes longer, e.g. because the shared memory
+/// of the VFS is wedged.
+///
+/// A thread blocked in usvfs can't be cancelled, so on a timeout the
+/// operation keeps running in the background and whatever it does once it
+/// gets through still happens: a link made late is still made and recorded.
+/// Its result is thrown away. A wedged VFS keeps one thread per timed out call
+/// around, so recreate the VFS rather than retrying in a loop
+pub fn with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    operation: impl FnOnce() -> Result<T, UsvfsError> + Send + 'static,
+) -> Result<T, UsvfsError> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // the receiver is gone if the wait timed out, nobody wants the result
+        _ = sender.send(operation());
+    });
+    receiver
+        .recv_timeout(timeout)
+        .map_err(|_| UsvfsError::TimedOut)?
+}
+
+/// virtually_link_file() giving up after timeout, see with_timeout()
+pub fn virtually_link_file_timeout(
+    source: impl Into<PathBuf>,
+    destination: impl Into<PathBuf>,
+    flags: LinkFlags,
+    timeout: Duration,
+) -> Result<(), UsvfsError> {
+    let (source, destination) = (source.into(), destination.into());
+    with_timeout(timeout, move || {
+        virtually_link_file(source, destination, flags)
+    })
+}
+
+/// virtually_link_directory_static() giving up after timeout, see with_timeout()
+pub fn virtually_link_directory_static_timeout(
+    source: impl Into<PathBuf>,
+    destination: impl Into<PathBuf>,
+    flags: LinkFlags,
+    timeout: Duration,
+) -> Result<(), UsvfsError> {
+    let (source, destination) = (source.into(), destination.into());
+    with_timeout(timeout, move || {
+        virtually_link_directory_static(source, destination, flags)
+    })
+}
+
+/// vfs_dump() giving up after timeout, see with_timeout()
+pub fn vfs_dump_timeout(timeout: Duration) -> Result<String, UsvfsError> {
+    with_timeout(timeout, vfs_dump)
+}
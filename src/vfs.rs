@@ -0,0 +1,384 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    clear_virtual_mappings, connect_vfs, create_vfs, disconnect_vfs, link_bytes, lock_rules,
+    mappings_generation, parse_vfs_dump, prefixed_path, recorded_mappings, reset_virtual_mappings,
+    vfs_dump, virtually_link_directory_static, virtually_link_directory_static_filtered,
+    virtually_link_directory_static_listed, virtually_link_directory_static_progress,
+    virtually_link_file, HookedCommand, LinkFlags, LinkedTempFile, MappingKind, MappingRecord,
+    ParametersHandle, UsvfsError, VfsConfig, VfsTree, MAPPINGS,
+};
+
+/// A live connection to a VFS. The session owns the parameters it
+/// was created from and disconnects when dropped, so mappings can
+/// only be made while it is alive and the parameters are only freed
+/// after the VFS is closed.
+///
+/// Please note that you can only be connected to one vfs, so creating
+/// a second session will silently disconnect the first one.
+///
+/// # Threading
+/// The connection is process wide rather than tied to the thread that made
+/// it, and usvfs serializes access to the mapping tree with its own
+/// interprocess lock. The session is therefore Send, so it can be created
+/// on one thread and used or dropped on another. It is not Sync: mappings
+/// take &mut self and the parameters it owns aren't synchronized, so share
+/// it behind a Mutex if several threads need to link. The free functions
+/// like drain_log_messages() don't need the session and can run on a
+/// background thread while another thread owns it.
+pub struct Vfs {
+    params: ParametersHandle,
+    /// the directory relative sources are resolved against, see with_base()
+    base: Option<PathBuf>,
+    /// the parsed dump resolve_virtual() looks paths up in, together with
+    /// the mappings_generation() it was made at
+    tree: RefCell<Option<(u64, VfsTree)>>,
+}
+
+impl Vfs {
+    /// creates a new vfs from the parameters, guaranteeing
+    /// it is reset before use. See create_vfs()
+    pub fn create(params: ParametersHandle) -> Result<Self, UsvfsError> {
+        create_vfs(&params)?;
+        Ok(Self::new(params))
+    }
+
+    /// connect to a virtual filesystem as a controller, without resetting
+    /// it and without hooking the calling process. See connect_vfs()
+    pub fn connect(params: ParametersHandle) -> Result<Self, UsvfsError> {
+        connect_vfs(&params)?;
+        Ok(Self::new(params))
+    }
+
+    /// a session for the VFS params was just created or connected with
+    fn new(params: ParametersHandle) -> Self {
+        Self {
+            params,
+            base: None,
+            tree: RefCell::new(None),
+        }
+    }
+
+    /// connect to the VFS another process created, knowing only its instance
+    /// name. The other parameters are left at their defaults, which is fine
+    /// since they only take effect when a VFS is created: the log level,
+    /// debug mode and crash dump settings stay whatever its creator set
+    pub fn connect_by_name(instance_name: &str) -> Result<Self, UsvfsError> {
+        let params = ParametersHandle::builder()
+            .instance_name(instance_name)
+            .build()?;
+        Self::connect(params)
+    }
+
+    /// the parameters this session was created with
+    pub fn parameters(&self) -> &ParametersHandle {
+        &self.params
+    }
+
+    /// resolve relative link sources against base, like the install folder
+    /// of a mod. Absolute sources are linked unchanged.
+    ///
+    /// The base is canonicalized once here, so symlinks and junctions in it
+    /// are followed at this point and later changes to them don't move
+    /// where relative sources point. Fails with Io if base doesn't exist,
+    /// leaving the session connected and its previous base in place
+    pub fn with_base(&mut self, base: PathBuf) -> Result<&mut Self, UsvfsError> {
+        self.base = Some(canonical_base(&base)?);
+        Ok(self)
+    }
+
+    /// the canonical base directory set with with_base()
+    pub fn base(&self) -> Option<&Path> {
+        self.base.as_deref()
+    }
+
+    /// source joined to the base if it is relative and a base is set
+    pub fn resolve_source(&self, source: impl AsRef<Path>) -> PathBuf {
+        resolve_against(self.base.as_deref(), source.as_ref())
+    }
+
+    /// link a file virtually, see virtually_link_file()
+    pub fn link_file(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+    ) -> Result<(), UsvfsError> {
+        virtually_link_file(self.resolve_source(source), destination, flags)
+    }
+
+    /// link a directory virtually, see virtually_link_directory_static()
+    pub fn link_directory_static(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+    ) -> Result<(), UsvfsError> {
+        virtually_link_directory_static(self.resolve_source(source), destination, flags)
+    }
+
+    /// link the files below a directory that predicate accepts one by one,
+    /// see virtually_link_directory_static_filtered()
+    pub fn link_directory_static_filtered(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+        predicate: impl Fn(&Path) -> bool,
+    ) -> Result<(), UsvfsError> {
+        virtually_link_directory_static_filtered(
+            self.resolve_source(source),
+            destination,
+            flags,
+            predicate,
+        )
+    }
+
+    /// link every file below a directory one by one and report progress,
+    /// see virtually_link_directory_static_progress()
+    pub fn link_directory_static_progress(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+        progress: impl FnMut(usize, usize),
+    ) -> Result<(), UsvfsError> {
+        virtually_link_directory_static_progress(
+            self.resolve_source(source),
+            destination,
+            flags,
+            progress,
+        )
+    }
+
+    /// link the files below a directory the skip lists don't cover one by
+    /// one and return their destinations, see
+    /// virtually_link_directory_static_listed()
+    pub fn link_directory_static_listed(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+    ) -> Result<Vec<PathBuf>, UsvfsError> {
+        virtually_link_directory_static_listed(self.resolve_source(source), destination, flags)
+    }
+
+    /// write contents to a temp file and link it to destination, see link_bytes()
+    pub fn link_bytes(
+        &mut self,
+        contents: &[u8],
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+    ) -> Result<LinkedTempFile, UsvfsError> {
+        link_bytes(contents, destination.as_ref(), flags)
+    }
+
+    /// removes all virtual mappings
+    pub fn clear_mappings(&mut self) {
+        clear_virtual_mappings()
+    }
+
+    /// removes all virtual mappings and returns how many there were, the
+    /// session stays connected. See reset_virtual_mappings()
+    pub fn reset_mappings(&mut self) -> usize {
+        reset_virtual_mappings()
+    }
+
+    /// every successful link made since the mappings were last cleared. Since
+    /// there is only one connection per process this is the same record the
+    /// free functions keep, see recorded_mappings(), so links made with
+    /// virtually_link_file() while the session is alive show up here too
+    pub fn mappings(&self) -> Vec<MappingRecord> {
+        recorded_mappings()
+    }
+
+    /// the real path the VFS redirects virtual_path to, None if it isn't
+    /// mapped or the dump can't be made or parsed. Like resolve(), but the
+    /// parsed dump is kept and only made again after links were made or
+    /// cleared through this crate, so looking up many paths is cheap.
+    /// Changes other controllers of the same VFS make aren't noticed, call
+    /// resolve() to always read the current tree
+    pub fn resolve_virtual(&self, virtual_path: &Path) -> Option<PathBuf> {
+        let mut tree = self.tree.borrow_mut();
+        let generation = mappings_generation();
+        if !matches!(&*tree, Some((made_at, _)) if *made_at == generation) {
+            let dump = vfs_dump().ok()?;
+            *tree = Some((generation, parse_vfs_dump(&dump).ok()?));
+        }
+        let (_, tree) = tree.as_ref()?;
+        tree.find(virtual_path)?.source.clone()
+    }
+
+    /// the number of entries mappings() would return
+    pub fn mapping_count(&self) -> usize {
+        lock_rules(&MAPPINGS).len()
+    }
+}
+
+impl Drop for Vfs {
+    fn drop(&mut self) {
+        disconnect_vfs()
+    }
+}
+
+/// Records the links a Vfs would make without making them, to preview or
+/// compare a mod setup before it touches the real VFS. It doesn't need a
+/// VFS to exist, and the plan it records holds the same MappingRecords
+/// a Vfs with the same base would, so applying each of them with
+/// MappingRecord::apply() makes the planned links
+#[derive(Debug, Clone, Default)]
+pub struct DryRunVfs {
+    base: Option<PathBuf>,
+    plan: Vec<MappingRecord>,
+}
+
+impl DryRunVfs {
+    /// a dry run without a base that hasn't recorded anything yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// resolve relative sources against base, see Vfs::with_base(). Keeps
+    /// the recorded plan and the previous base if base doesn't exist
+    pub fn with_base(&mut self, base: PathBuf) -> Result<&mut Self, UsvfsError> {
+        self.base = Some(canonical_base(&base)?);
+        Ok(self)
+    }
+
+    /// the canonical base directory set with with_base()
+    pub fn base(&self) -> Option<&Path> {
+        self.base.as_deref()
+    }
+
+    /// record linking a file, see Vfs::link_file()
+    pub fn link_file(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+    ) -> Result<(), UsvfsError> {
+        self.record(
+            MappingKind::File,
+            source.as_ref(),
+            destination.as_ref(),
+            flags,
+        )
+    }
+
+    /// record linking a directory, see Vfs::link_directory_static()
+    pub fn link_directory_static(
+        &mut self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        flags: LinkFlags,
+    ) -> Result<(), UsvfsError> {
+        self.record(
+            MappingKind::DirectoryStatic,
+            source.as_ref(),
+            destination.as_ref(),
+            flags,
+        )
+    }
+
+    /// the links recorded so far, in the order they were made
+    pub fn plan(&self) -> &[MappingRecord] {
+        &self.plan
+    }
+
+    /// the links recorded, in the order they were made
+    pub fn into_plan(self) -> Vec<MappingRecord> {
+        self.plan
+    }
+
+    /// record the mapping the link would, rejecting the same flags and
+    /// prefixing long sources the same way, without calling usvfs
+    fn record(
+        &mut self,
+        kind: MappingKind,
+        source: &Path,
+        destination: &Path,
+        flags: LinkFlags,
+    ) -> Result<(), UsvfsError> {
+        flags.validate_for(kind)?;
+        self.plan.push(MappingRecord {
+            source: prefixed_path(&resolve_against(self.base.as_deref(), source)),
+            destination: destination.to_path_buf(),
+            flags,
+            kind,
+        });
+        Ok(())
+    }
+}
+
+/// source joined to base if it is relative and there is a base
+fn resolve_against(base: Option<&Path>, source: &Path) -> PathBuf {
+    match base {
+        Some(base) if source.is_relative() => base.join(source),
+        _ => source.to_path_buf(),
+    }
+}
+
+/// the canonical form of a base directory, see Vfs::with_base()
+fn canonical_base(base: &Path) -> Result<PathBuf, UsvfsError> {
+    let canonical = std::fs::canonicalize(base).map_err(|error| UsvfsError::Io {
+        kind: error.kind(),
+        message: format!("{}: {error}", base.display()),
+    })?;
+    Ok(strip_verbatim(canonical))
+}
+
+/// canonicalize() returns `\\?\C:\...` paths, drop the prefix again for
+/// paths on a drive so recorded sources look like the ones passed in.
+/// Verbatim UNC paths are kept, their plain form would need rewriting
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let stripped = path
+        .to_str()
+        .and_then(|text| text.strip_prefix(r"\\?\"))
+        .filter(|rest| rest.as_bytes().get(1) == Some(&b':'))
+        .map(PathBuf::from);
+    stripped.unwrap_or(path)
+}
+
+/// the whole launch in one call: create a VFS from config, make every link
+/// in mappings, spawn command into it, wait for it to exit and return its
+/// exit code. The VFS is disconnected again however this ends.
+///
+/// Each step fails with its own error, so the caller can tell them apart:
+/// EmptyInstanceName, NameTooLong or InteriorNul for a bad config,
+/// CreateFailed, AlreadyExists or AccessDenied for creating the VFS,
+/// LinkFailed for a mapping, ProcessSpawnFailed for spawning and WaitFailed
+/// for waiting. The exit code is returned as the i32 Windows reports, e.g.
+/// -1073741819 for an access violation
+pub fn run_in_vfs(
+    config: VfsConfig,
+    mappings: &[MappingRecord],
+    command: HookedCommand,
+) -> Result<i32, UsvfsError> {
+    if config.instance_name.is_empty() {
+        return Err(UsvfsError::EmptyInstanceName);
+    }
+    let params = ParametersHandle::try_from(config)?;
+
+    with_vfs(params, |_| {
+        for mapping in mappings {
+            mapping.apply()?;
+        }
+        let process = command.spawn()?;
+        process.wait().map(|code| code as i32)
+    })?
+}
+
+/// create a vfs from params, run f with the session and disconnect
+/// afterwards. The session is dropped even if f panics, since unwinding
+/// runs its Drop, so the connection can't outlive the call. This only
+/// holds with panic = "unwind", aborting skips every destructor
+pub fn with_vfs<T>(
+    params: ParametersHandle,
+    f: impl FnOnce(&mut Vfs) -> T,
+) -> Result<T, UsvfsError> {
+    let mut vfs = Vfs::create(params)?;
+    Ok(f(&mut vfs))
+}